@@ -0,0 +1,133 @@
+//! The `#[derive(ArgumentType)]` macro for adante, re-exported by the
+//! `adante` crate when its `derive` feature is enabled.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, Data, DeriveInput, Expr, ExprLit, Fields, Lit, LitStr, Meta, Token};
+
+/// Derives `ArgumentType` for an enum of unit variants, each listing the
+/// keys it's written as in a `#[key(..)]` attribute.
+///
+/// Like `arg_enum!`, the generated impl covers `from_str`, `known_keys`,
+/// `variants`, `canonical_key` (the last key listed), `describe` (the
+/// variant's doc comment) and `bit` (the variant's position). A variant
+/// without a `#[key(..)]` attribute is a compile error, so none can be left
+/// unparseable by accident.
+///
+/// ```
+/// use adante::{ArgumentType, ParseError};
+/// use adante_derive::ArgumentType;
+///
+/// #[derive(Debug, Clone, Copy, PartialEq, ArgumentType)]
+/// enum FlagType {
+///     /// Print this help
+///     #[key("-h", "--help")]
+///     Help,
+/// }
+///
+/// assert_eq!(FlagType::from_str("-h", ParseError::UnknownFlag), Ok(FlagType::Help));
+/// assert_eq!(FlagType::Help.describe(), "Print this help");
+/// ```
+#[proc_macro_derive(ArgumentType, attributes(key))]
+pub fn derive_argument_type(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+// A variant along with the keys and description read from its attributes
+struct Variant {
+    ident: syn::Ident,
+    keys: Vec<LitStr>,
+    doc: String,
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let data = match &input.data {
+        Data::Enum(data) => data,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "ArgumentType can only be derived for enums",
+            ))
+        }
+    };
+    let variants = data.variants.iter().map(read_variant).collect::<syn::Result<Vec<_>>>()?;
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let idents: Vec<_> = variants.iter().map(|variant| &variant.ident).collect();
+    let keys: Vec<_> = variants.iter().map(|variant| &variant.keys).collect();
+    let canonical: Vec<_> = variants.iter().map(|variant| variant.keys.last()).collect();
+    let docs: Vec<_> = variants.iter().map(|variant| variant.doc.trim()).collect();
+    let bits = 0..variants.len() as u32;
+
+    Ok(quote! {
+        impl #impl_generics ::adante::ArgumentType for #name #ty_generics #where_clause {
+            fn from_str<E: ::adante::Error>(key: &str, error: E) -> Result<Self, E> {
+                match key {
+                    #(#(#keys)|* => Ok(Self::#idents),)*
+                    _ => Err(error),
+                }
+            }
+            fn known_keys() -> &'static [&'static str] {
+                &[#(#(#keys),*),*]
+            }
+            fn canonical_key(&self) -> &'static str {
+                match self {
+                    #(Self::#idents => #canonical,)*
+                }
+            }
+            fn describe(&self) -> &'static str {
+                match self {
+                    #(Self::#idents => #docs,)*
+                }
+            }
+            fn variants() -> &'static [Self] {
+                &[#(Self::#idents),*]
+            }
+            fn bit(&self) -> u32 {
+                match self {
+                    #(Self::#idents => #bits,)*
+                }
+            }
+        }
+    })
+}
+
+fn read_variant(variant: &syn::Variant) -> syn::Result<Variant> {
+    if !matches!(variant.fields, Fields::Unit) {
+        return Err(syn::Error::new_spanned(
+            &variant.ident,
+            "ArgumentType can only be derived for variants without fields",
+        ));
+    }
+    let mut keys = Vec::new();
+    let mut doc = String::new();
+    for attr in &variant.attrs {
+        if attr.path().is_ident("key") {
+            let list = attr.parse_args_with(Punctuated::<LitStr, Token![,]>::parse_terminated)?;
+            keys.extend(list);
+        } else if attr.path().is_ident("doc") {
+            if let Meta::NameValue(meta) = &attr.meta {
+                if let Expr::Lit(ExprLit { lit: Lit::Str(line), .. }) = &meta.value {
+                    doc.push_str(&line.value());
+                }
+            }
+        }
+    }
+    if keys.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &variant.ident,
+            "missing #[key(..)] attribute listing the keys for this variant",
+        ));
+    }
+    Ok(Variant {
+        ident: variant.ident.clone(),
+        keys,
+        doc,
+    })
+}
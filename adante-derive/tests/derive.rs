@@ -0,0 +1,47 @@
+use adante::{ArgumentType, Arguments, ParseError};
+use adante_derive::ArgumentType;
+
+#[derive(Debug, Clone, Copy, PartialEq, ArgumentType)]
+enum FlagType {
+    /// Print this help
+    #[key("-h", "--help")]
+    Help,
+    #[key("-v", "--verbose")]
+    Verbose,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ArgumentType)]
+enum ActionType {
+    #[key("a", "add")]
+    Add,
+    #[key("r", "remove")]
+    Remove,
+}
+
+#[test]
+fn derived_from_str() {
+    assert_eq!(FlagType::from_str("-h", ParseError::UnknownFlag), Ok(FlagType::Help));
+    assert_eq!(FlagType::from_str("--verbose", ParseError::UnknownFlag), Ok(FlagType::Verbose));
+    assert_eq!(FlagType::from_str("-q", ParseError::UnknownFlag), Err(ParseError::UnknownFlag));
+}
+
+#[test]
+fn derived_metadata() {
+    assert_eq!(FlagType::known_keys(), &["-h", "--help", "-v", "--verbose"]);
+    assert_eq!(FlagType::variants(), &[FlagType::Help, FlagType::Verbose]);
+    assert_eq!(ActionType::Remove.canonical_key(), "remove");
+    assert_eq!(FlagType::Help.describe(), "Print this help");
+    assert_eq!(FlagType::Verbose.describe(), "");
+    assert_eq!(ActionType::Remove.bit(), 1);
+}
+
+#[test]
+fn derived_parse() {
+    let args: Arguments<FlagType, ActionType> =
+        Arguments::parse(vec!["-v", "add", "r"], ParseError::UnknownFlag).unwrap();
+    assert_eq!(args.flags[0].key, FlagType::Verbose);
+    assert_eq!(args.actions, vec![ActionType::Add, ActionType::Remove]);
+
+    let result = Arguments::<FlagType, ActionType>::parse(vec!["edit"], ParseError::UnknownFlag);
+    assert_eq!(result.unwrap_err(), ParseError::UnknownAction);
+}
@@ -0,0 +1,91 @@
+use adante::{ArgumentType, Arguments, Error, ParseError};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FlagType {
+    Help,
+    Verbose,
+    Output,
+    Define,
+}
+impl ArgumentType for FlagType {
+    fn from_str<ErrorType>(key: &str, error: ErrorType) -> Result<Self, ErrorType> {
+        match key {
+            "-h" | "--help" => Ok(Self::Help),
+            "-v" | "--verbose" => Ok(Self::Verbose),
+            "-o" | "--output" => Ok(Self::Output),
+            "-D" | "--define" => Ok(Self::Define),
+            _ => Err(error),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ActionType {
+    Add,
+    Remove,
+}
+impl ArgumentType for ActionType {
+    fn from_str<ErrorType>(key: &str, error: ErrorType) -> Result<Self, ErrorType> {
+        match key {
+            "add" | "a" => Ok(Self::Add),
+            "remove" | "r" => Ok(Self::Remove),
+            _ => Err(error),
+        }
+    }
+}
+
+// An error carrying data, which the parse shouldn't clone unless it fails
+#[derive(Debug, Clone)]
+struct MessageError(String);
+
+impl Error for MessageError {
+    fn handle(&self) {}
+    fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+// 10k arguments mixing valueless flags, valued flags and actions
+fn synthetic_args() -> Vec<String> {
+    (0..10_000)
+        .map(|i| match i % 5 {
+            0 => "-v".to_string(),
+            1 => format!("--output=build/target-{}.out", i),
+            2 => format!("-D=key{}=value{}", i, i),
+            3 => "add".to_string(),
+            _ => "--help".to_string(),
+        })
+        .collect()
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let owned = synthetic_args();
+    let env_args: Vec<&str> = owned.iter().map(String::as_str).collect();
+
+    c.bench_function("parse 10k", |b| {
+        b.iter(|| {
+            let args: Arguments<FlagType, ActionType> =
+                Arguments::parse(black_box(env_args.clone()), ParseError::UnknownFlag).unwrap();
+            args
+        })
+    });
+    c.bench_function("parse 10k, String error", |b| {
+        b.iter(|| {
+            let error = MessageError("unknown argument".to_string());
+            let args: Arguments<FlagType, ActionType> =
+                Arguments::parse(black_box(env_args.clone()), error).unwrap();
+            args
+        })
+    });
+    c.bench_function("parse_borrowed 10k", |b| {
+        b.iter(|| {
+            let args: Arguments<FlagType, ActionType, &str> =
+                Arguments::parse_borrowed(black_box(&env_args), ParseError::UnknownFlag).unwrap();
+            args
+        })
+    });
+}
+
+criterion_group!(benches, bench_parse);
+criterion_main!(benches);
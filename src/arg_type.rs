@@ -0,0 +1,534 @@
+//! The `ArgumentType` trait, implemented by the user's flag and action
+//! types.
+//!
+//! It is re-exported at the crate root, so `adante::ArgumentType` and
+//! `adante::arg_type::ArgumentType` name the same trait.
+//!
+//! ```
+//! fn takes_argument_type<T: adante::arg_type::ArgumentType>() {}
+//! fn takes_root_argument_type<T: adante::ArgumentType>() {}
+//! ```
+
+use crate::{Error, ParseError};
+use alloc::format;
+
+/// The prefix a key was written with, passed to `ArgumentType::from_key`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyStyle {
+    /// A flag written with a single dash, such as `-h`
+    Short,
+    /// A flag written with two dashes, such as `--help`
+    Long,
+    /// An action, written without any dash
+    Action,
+}
+
+/// A trait describing the shared methods of both Flags and Arguments
+pub trait ArgumentType {
+    /// A user implemented function that takes a string as input and returns an
+    /// argument type.
+    ///
+    /// # Examples
+    /// ```
+    /// use adante::{ArgumentType, Error};
+    ///
+    /// #[derive(Debug, Clone, Copy)]
+    /// enum ErrorType {
+    ///     Syntax, // EXTREMELY simple example
+    ///             // More complex examples are shown in
+    ///             // the documentation for Error
+    /// }
+    /// impl Error for ErrorType {
+    ///     fn handle(&self) {
+    ///         ()
+    ///     }
+    ///     fn as_str(&self) -> &str {
+    ///         "Syntax Error"
+    ///     }
+    /// }
+    ///
+    ///
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum FlagType {
+    ///     Help,
+    ///     Verbose,
+    ///     Print,
+    ///     TestFail, // NOTE: For testing only
+    ///               // Use Error
+    /// }
+    /// impl ArgumentType for FlagType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "-h" | "--help" => Ok(Self::Help),
+    ///             "-v" | "--verbose" => Ok(Self::Verbose),
+    ///             "-p" | "--print" => Ok(Self::Print),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    /// }
+    /// let result = match FlagType::from_str("-v", ErrorType::Syntax) {
+    ///     Ok(t) => t,
+    ///     Err(_) => FlagType::TestFail // In actual usecase this would pipe into
+    ///                                  // An Error's handle function
+    /// };
+    /// assert_eq!(result, FlagType::Verbose)
+    ///
+    /// ```
+    fn from_str<E: Error>(key: &str, error: E) -> Result<Self, E>
+    where
+        Self: core::marker::Sized;
+    /// A user implemented function like `from_str` that also receives the
+    /// value given with `=`, if any, so that invalid key/value combinations
+    /// can be rejected while parsing. Defaults to calling `from_str`.
+    ///
+    /// # Examples
+    /// ```
+    /// use adante::{ArgumentType, Error};
+    ///
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum FlagType {
+    ///     Level,
+    /// }
+    /// impl ArgumentType for FlagType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "-l" | "--level" => Ok(Self::Level),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    ///     fn from_str_with_value<E: Error>(key: &str, value: Option<&str>, error: E)
+    ///                                -> Result<Self, E> {
+    ///         match value.map(|v| v.parse::<u8>()) {
+    ///             Some(Ok(level)) if level <= 3 => Self::from_str(key, error),
+    ///             Some(_) => Err(error),
+    ///             None => Self::from_str(key, error),
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    fn from_str_with_value<E: Error>(key: &str, value: Option<&str>, error: E) -> Result<Self, E>
+    where
+        Self: core::marker::Sized,
+    {
+        let _ = value;
+        Self::from_str(key, error)
+    }
+    /// A user implemented function for actions that carry data, such as
+    /// `Goto(usize)`. It receives the action's key along with the argument
+    /// that follows it, if any. Defaults to calling `from_str`.
+    ///
+    /// The parser only calls this for an action key that `from_str`
+    /// rejects, and when it succeeds, the following argument is consumed.
+    ///
+    /// # Examples
+    /// ```
+    /// use adante::{ArgumentType, Error};
+    ///
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum ActionType {
+    ///     Add,
+    ///     Goto(usize),
+    /// }
+    /// impl ArgumentType for ActionType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "add" => Ok(Self::Add),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    ///     fn from_str_with_arg<E: Error>(key: &str, arg: Option<&str>, error: E)
+    ///                                -> Result<Self, E> {
+    ///         match (key, arg.map(str::parse)) {
+    ///             ("goto", Some(Ok(line))) => Ok(Self::Goto(line)),
+    ///             _ => Self::from_str(key, error),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let result = ActionType::from_str_with_arg("goto", Some("5"), adante::ParseError::UnknownAction);
+    /// assert_eq!(result, Ok(ActionType::Goto(5)));
+    /// ```
+    fn from_str_with_arg<E: Error>(key: &str, arg: Option<&str>, error: E) -> Result<Self, E>
+    where
+        Self: core::marker::Sized,
+    {
+        let _ = arg;
+        Self::from_str(key, error)
+    }
+    /// A user implemented function that lists every key `from_str` accepts,
+    /// such as `["-h", "--help"]`. Defaults to an empty list.
+    ///
+    /// The parser uses this to check tokens before reinterpreting them, for
+    /// example when expanding short flag clusters (see
+    /// `ParseOptions::short_clusters`).
+    ///
+    /// # Examples
+    /// ```
+    /// use adante::ArgumentType;
+    ///
+    /// enum FlagType {
+    ///     Help,
+    ///     Verbose,
+    /// }
+    /// impl ArgumentType for FlagType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "-h" | "--help" => Ok(Self::Help),
+    ///             "-v" | "--verbose" => Ok(Self::Verbose),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    ///     fn known_keys() -> &'static [&'static str] {
+    ///         &["-h", "--help", "-v", "--verbose"]
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(FlagType::known_keys().len(), 4);
+    /// ```
+    fn known_keys() -> &'static [&'static str] {
+        &[]
+    }
+    /// A user implemented function like `from_str` that receives the key
+    /// without its dashes, along with the style it was written in. Only
+    /// called when `ParseOptions::strip_prefix` is enabled.
+    ///
+    /// Defaults to putting the dashes back and calling `from_str`, so
+    /// implementing it is optional.
+    ///
+    /// # Examples
+    /// ```
+    /// use adante::{ArgumentType, Error, KeyStyle};
+    ///
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum FlagType {
+    ///     Help,
+    /// }
+    /// impl ArgumentType for FlagType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         Err(error)
+    ///     }
+    ///     fn from_key<E: Error>(key: &str, style: KeyStyle, error: E) -> Result<Self, E> {
+    ///         match (key, style) {
+    ///             ("h", KeyStyle::Short) | ("help", KeyStyle::Long) => Ok(Self::Help),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    fn from_key<E: Error>(key: &str, style: KeyStyle, error: E) -> Result<Self, E>
+    where
+        Self: core::marker::Sized,
+    {
+        match style {
+            KeyStyle::Short => Self::from_str(&format!("-{}", key), error),
+            KeyStyle::Long => Self::from_str(&format!("--{}", key), error),
+            KeyStyle::Action => Self::from_str(key, error),
+        }
+    }
+    /// A user implemented function that returns whether a flag can be given
+    /// a value with `=`. Defaults to `true`.
+    ///
+    /// When this returns `false`, `Arguments::parse` rejects `-v=x` with the
+    /// error returned by `Error::unexpected_value` instead of storing `"x"`.
+    ///
+    /// # Examples
+    /// ```
+    /// use adante::ArgumentType;
+    ///
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum FlagType {
+    ///     Help,
+    ///     Verbose,
+    /// }
+    /// impl ArgumentType for FlagType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "-h" | "--help" => Ok(Self::Help),
+    ///             "-v" | "--verbose" => Ok(Self::Verbose),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    ///     fn takes_value(&self) -> bool {
+    ///         match self {
+    ///             Self::Help => true,
+    ///             Self::Verbose => false,
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// assert!(FlagType::Help.takes_value());
+    /// assert!(!FlagType::Verbose.takes_value());
+    /// ```
+    fn takes_value(&self) -> bool {
+        true
+    }
+    /// A user implemented function that returns whether a flag collects
+    /// `name=value` definitions, like the `-D` of many build tools.
+    /// Defaults to `false`.
+    ///
+    /// A map flag's key must be two characters long, such as `-D`. Its value
+    /// can then be attached directly, as in `-Dname=value`, and the pairs
+    /// are gathered by `Arguments::map_values`.
+    ///
+    /// # Examples
+    /// ```
+    /// use adante::ArgumentType;
+    ///
+    /// enum FlagType {
+    ///     Define,
+    ///     Verbose,
+    /// }
+    /// impl ArgumentType for FlagType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "-D" => Ok(Self::Define),
+    ///             "-v" | "--verbose" => Ok(Self::Verbose),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    ///     fn is_map(&self) -> bool {
+    ///         matches!(self, Self::Define)
+    ///     }
+    /// }
+    ///
+    /// assert!(FlagType::Define.is_map());
+    /// ```
+    fn is_map(&self) -> bool {
+        false
+    }
+    /// A user implemented function that returns whether a flag's value is
+    /// a secret, such as a token or password, to be masked by
+    /// `Flag::redacted_debug` and `Arguments::redacted_debug`. The value
+    /// itself is stored as usual. Defaults to `false`.
+    fn sensitive(&self) -> bool {
+        false
+    }
+    /// A user implemented function that returns a message if a flag is
+    /// deprecated, such as `"use --color instead"`. Such flags still parse
+    /// as usual, but are reported to the hook set with
+    /// `ParseOptions::on_warning`. Defaults to `None`.
+    ///
+    /// # Examples
+    /// ```
+    /// use adante::ArgumentType;
+    ///
+    /// enum FlagType {
+    ///     Color,
+    ///     Colour,
+    /// }
+    /// impl ArgumentType for FlagType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "--color" => Ok(Self::Color),
+    ///             "--colour" => Ok(Self::Colour),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    ///     fn deprecated(&self) -> Option<&'static str> {
+    ///         match self {
+    ///             Self::Colour => Some("use --color instead"),
+    ///             _ => None,
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(FlagType::Colour.deprecated(), Some("use --color instead"));
+    /// ```
+    fn deprecated(&self) -> Option<&'static str> {
+        None
+    }
+    /// A user implemented function that lists alias keys along with the
+    /// flag key each stands for, such as `("--colour", "--color")`, so that
+    /// `from_str` only has to match the canonical keys. Aliases are looked
+    /// up as written, dashes included, before `from_str` is called.
+    /// Defaults to an empty list.
+    ///
+    /// Actions can be aliased with `ParseOptions::aliases` instead.
+    ///
+    /// # Examples
+    /// ```
+    /// use adante::{ArgumentType, Arguments, ParseError};
+    ///
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum FlagType {
+    ///     Color,
+    /// }
+    /// impl ArgumentType for FlagType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "--color" => Ok(Self::Color),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    ///     fn aliases() -> &'static [(&'static str, &'static str)] {
+    ///         &[("--colour", "--color")]
+    ///     }
+    /// }
+    ///
+    /// let args: Arguments<FlagType, FlagType> =
+    ///     Arguments::parse(vec!["--colour=never"], ParseError::UnknownFlag).unwrap();
+    /// assert_eq!(args.flags[0].key, FlagType::Color);
+    /// ```
+    fn aliases() -> &'static [(&'static str, &'static str)] {
+        &[]
+    }
+    /// A user implemented function that returns the bit a variant is
+    /// stored at in a `FlagSet`, from 0 to 63, usually its position in the
+    /// enum. `arg_enum!` and the derive macro implement it that way.
+    /// Defaults to `u32::MAX`, which no `FlagSet` can hold, so
+    /// `Arguments::flag_set` returns `None` for types that don't implement
+    /// it.
+    fn bit(&self) -> u32 {
+        u32::MAX
+    }
+    /// A user implemented function that returns a one line description of
+    /// a variant for help text, such as `"Print this help"`. Defaults to an
+    /// empty string.
+    fn describe(&self) -> &'static str {
+        ""
+    }
+    /// A user implemented function that returns the key a variant is
+    /// written back as, such as `"--verbose"` for a flag or `"add"` for an
+    /// action. Used by `Flag::render` and `Arguments::to_args`. Defaults to
+    /// an empty string.
+    ///
+    /// # Examples
+    /// ```
+    /// use adante::ArgumentType;
+    ///
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum FlagType {
+    ///     Verbose,
+    /// }
+    /// impl ArgumentType for FlagType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "-v" | "--verbose" => Ok(Self::Verbose),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    ///     fn canonical_key(&self) -> &'static str {
+    ///         match self {
+    ///             Self::Verbose => "--verbose",
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(FlagType::Verbose.canonical_key(), "--verbose");
+    /// ```
+    fn canonical_key(&self) -> &'static str {
+        ""
+    }
+    /// A user implemented function that lists every variant of the type,
+    /// used along with `known_keys` to build help text such as
+    /// `Arguments::usage`. Defaults to an empty list.
+    ///
+    /// # Examples
+    /// ```
+    /// use adante::ArgumentType;
+    ///
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum FlagType {
+    ///     Help,
+    ///     Verbose,
+    /// }
+    /// impl ArgumentType for FlagType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "-h" | "--help" => Ok(Self::Help),
+    ///             "-v" | "--verbose" => Ok(Self::Verbose),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    ///     fn variants() -> &'static [Self] {
+    ///         &[Self::Help, Self::Verbose]
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(FlagType::variants(), &[FlagType::Help, FlagType::Verbose]);
+    /// ```
+    fn variants() -> &'static [Self]
+    where
+        Self: core::marker::Sized + 'static,
+    {
+        &[]
+    }
+}
+
+/// A trait for flag and action types that know their own error type, so
+/// that `Arguments::parse2` needs no error value.
+///
+/// Every `ArgumentType` also implements `ParseKey`, reporting the error
+/// `Arguments::parse` would: `ParseError::UnknownFlag` for keys starting
+/// with `-`, `ParseError::UnknownAction` for other keys and a lone `-`,
+/// `ParseError::EmptyArg` for an empty key, and
+/// `ParseError::UnexpectedValue` for a value given to a flag that can't
+/// take one. A type can therefore implement one trait or the other, but not
+/// both.
+///
+/// # Examples
+/// ```
+/// use adante::{ArgumentType, ParseError, ParseKey};
+///
+/// #[derive(Debug, Clone, Copy, PartialEq)]
+/// enum FlagType {
+///     Help,
+/// }
+/// impl ArgumentType for FlagType {
+///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+///                                -> Result<Self, ErrorType> {
+///         match key {
+///             "-h" | "--help" => Ok(Self::Help),
+///             _ => Err(error),
+///         }
+///     }
+/// }
+///
+/// assert_eq!(FlagType::parse_key("--help"), Ok(FlagType::Help));
+/// assert_eq!(FlagType::parse_key("--frob"), Err(ParseError::UnknownFlag));
+/// ```
+pub trait ParseKey: Sized {
+    /// The error returned for a key that isn't recognized
+    type Err: Error;
+    /// A user implemented function that turns a key into the type, or
+    /// returns the type's own error.
+    fn parse_key(key: &str) -> Result<Self, Self::Err>;
+    /// A user implemented function like `parse_key`, for a flag given
+    /// with a value after `=`. Defaults to calling `parse_key`, so any flag
+    /// can take a value.
+    fn parse_key_with_value(key: &str, value: Option<&str>) -> Result<Self, Self::Err> {
+        let _ = value;
+        Self::parse_key(key)
+    }
+}
+
+impl<T: ArgumentType> ParseKey for T {
+    type Err = ParseError;
+    fn parse_key(key: &str) -> Result<Self, ParseError> {
+        if key.is_empty() {
+            Err(ParseError::EmptyArg)
+        } else if key.starts_with('-') && key != "-" {
+            T::from_str(key, ParseError::UnknownFlag)
+        } else {
+            T::from_str(key, ParseError::UnknownAction)
+        }
+    }
+    fn parse_key_with_value(key: &str, value: Option<&str>) -> Result<Self, ParseError> {
+        let flag = T::from_str_with_value(key, value, ParseError::UnknownFlag)?;
+        if value.is_some() && !flag.takes_value() {
+            return Err(ParseError::UnexpectedValue);
+        }
+        Ok(flag)
+    }
+}
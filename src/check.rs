@@ -0,0 +1,55 @@
+//! Consistency checks for `ArgumentType` implementations, meant to be
+//! called from the tests of crates that implement it.
+
+use crate::{ArgumentType, Error};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Checks that every key in `T::known_keys` parses with `from_str`, and
+/// that the parsed variant's `canonical_key` parses back into the same
+/// variant. Returns a message naming each offending key otherwise.
+///
+/// `error` is only handed to `from_str`, so any value will do.
+///
+/// # Examples
+///
+/// ```
+/// use adante::common::CommonFlags;
+/// use adante::{check, ParseError};
+///
+/// assert_eq!(check::round_trip::<CommonFlags, _>(ParseError::UnknownFlag), Ok(()));
+/// ```
+pub fn round_trip<T, E>(error: E) -> Result<(), Vec<String>>
+where
+    T: ArgumentType + PartialEq,
+    E: Error + Clone,
+{
+    let mut problems = Vec::new();
+    for key in T::known_keys() {
+        let variant = match T::from_str(key, error.clone()) {
+            Ok(variant) => variant,
+            Err(_) => {
+                problems.push(format!("'{}' doesn't parse", key));
+                continue;
+            }
+        };
+        let canonical = variant.canonical_key();
+        match T::from_str(canonical, error.clone()) {
+            Ok(rendered) if rendered == variant => {}
+            Ok(_) => problems.push(format!(
+                "'{}' renders as '{}', which parses differently",
+                key, canonical
+            )),
+            Err(_) => problems.push(format!(
+                "'{}' renders as '{}', which doesn't parse",
+                key, canonical
+            )),
+        }
+    }
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems)
+    }
+}
@@ -0,0 +1,78 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+#[cfg(feature = "color")]
+use std::ffi::OsStr;
+
+// ANSI escapes for the styles used in rendered output
+pub(crate) const RED: &str = "\x1b[31m";
+pub(crate) const BOLD: &str = "\x1b[1m";
+pub(crate) const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+/// When `ParseFailure::render_with` and `Arguments::usage_with` add ANSI
+/// colors to their output.
+///
+/// Requires the `color` feature.
+#[cfg(feature = "color")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Color only if the output is a terminal and `NO_COLOR` isn't set, as
+    /// `ParseFailure::render` and `Arguments::usage` do
+    Auto,
+    /// Always color
+    Always,
+    /// Never color, giving the same text as without the `color` feature
+    Never,
+}
+
+#[cfg(feature = "color")]
+impl ColorChoice {
+    // Returns whether to color, given whether the output is a terminal
+    pub(crate) fn resolve(self, is_terminal: bool) -> bool {
+        self.resolve_with(is_terminal, std::env::var_os("NO_COLOR").as_deref())
+    }
+    // Returns whether to color, given whether the output is a terminal and
+    // the value of `NO_COLOR`, which turns color off unless it's empty
+    pub(crate) fn resolve_with(self, is_terminal: bool, no_color: Option<&OsStr>) -> bool {
+        match self {
+            ColorChoice::Auto => is_terminal && !matches!(no_color, Some(value) if !value.is_empty()),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+// Returns whether output to stderr is colored by default
+pub(crate) fn stderr_colored() -> bool {
+    #[cfg(feature = "color")]
+    {
+        use std::io::IsTerminal;
+        ColorChoice::Auto.resolve(std::io::stderr().is_terminal())
+    }
+    #[cfg(not(feature = "color"))]
+    {
+        false
+    }
+}
+
+// Returns whether output to stdout is colored by default
+pub(crate) fn stdout_colored() -> bool {
+    #[cfg(feature = "color")]
+    {
+        use std::io::IsTerminal;
+        ColorChoice::Auto.resolve(std::io::stdout().is_terminal())
+    }
+    #[cfg(not(feature = "color"))]
+    {
+        false
+    }
+}
+
+// Wraps the text in the style if enabled, leaving it untouched otherwise
+pub(crate) fn paint(text: &str, style: &str, enabled: bool) -> String {
+    if enabled {
+        format!("{}{}{}", style, text, RESET)
+    } else {
+        text.to_string()
+    }
+}
@@ -0,0 +1,91 @@
+//! Ready made flags shared by most command line tools.
+
+use crate::{ArgumentType, Error};
+
+/// The flags most small tools need, so a parser can be built without
+/// writing a flag enum: `Arguments::<CommonFlags, ActionType>::parse(..)`.
+///
+/// | Variant   | Keys                  |
+/// |-----------|-----------------------|
+/// | `Help`    | `-h`, `--help`        |
+/// | `Version` | `-V`, `--version`     |
+/// | `Verbose` | `-v`, `--verbose`     |
+/// | `Quiet`   | `-q`, `--quiet`       |
+///
+/// None of them take a value.
+///
+/// # Examples
+///
+/// A flag enum of your own can fall back to `CommonFlags` for the keys it
+/// doesn't handle itself:
+///
+/// ```
+/// use adante::common::CommonFlags;
+/// use adante::{ArgumentType, Error};
+///
+/// #[derive(Debug, Clone, Copy, PartialEq)]
+/// enum FlagType {
+///     Output,
+///     Common(CommonFlags),
+/// }
+/// impl ArgumentType for FlagType {
+///     fn from_str<E: Error>(key: &str, error: E) -> Result<Self, E> {
+///         match key {
+///             "-o" | "--output" => Ok(Self::Output),
+///             _ => CommonFlags::from_str(key, error).map(Self::Common),
+///         }
+///     }
+/// }
+///
+/// let result = FlagType::from_str("--quiet", adante::ParseError::UnknownFlag);
+/// assert_eq!(result, Ok(FlagType::Common(CommonFlags::Quiet)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CommonFlags {
+    /// `-h` or `--help`
+    Help,
+    /// `-V` or `--version`
+    Version,
+    /// `-v` or `--verbose`
+    Verbose,
+    /// `-q` or `--quiet`
+    Quiet,
+}
+
+impl ArgumentType for CommonFlags {
+    fn from_str<E: Error>(key: &str, error: E) -> Result<Self, E> {
+        match key {
+            "-h" | "--help" => Ok(Self::Help),
+            "-V" | "--version" => Ok(Self::Version),
+            "-v" | "--verbose" => Ok(Self::Verbose),
+            "-q" | "--quiet" => Ok(Self::Quiet),
+            _ => Err(error),
+        }
+    }
+    fn known_keys() -> &'static [&'static str] {
+        &["-h", "--help", "-V", "--version", "-v", "--verbose", "-q", "--quiet"]
+    }
+    fn takes_value(&self) -> bool {
+        false
+    }
+    fn describe(&self) -> &'static str {
+        match self {
+            Self::Help => "Print help",
+            Self::Version => "Print version",
+            Self::Verbose => "Print more output",
+            Self::Quiet => "Print less output",
+        }
+    }
+    fn canonical_key(&self) -> &'static str {
+        match self {
+            Self::Help => "--help",
+            Self::Version => "--version",
+            Self::Verbose => "--verbose",
+            Self::Quiet => "--quiet",
+        }
+    }
+    fn variants() -> &'static [Self] {
+        &[Self::Help, Self::Version, Self::Verbose, Self::Quiet]
+    }
+}
@@ -0,0 +1,178 @@
+use crate::token::{self, TokenKind};
+use crate::{ArgumentType, Arguments, ParseError, ParseOptions};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// A suggestion returned by `complete`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Completion {
+    /// A flag key, such as `--verbose`
+    Flag(&'static str),
+    /// An action key, such as `add`
+    Action(&'static str),
+    /// The previous argument is a flag that takes a value, which is
+    /// usually a file
+    File,
+}
+
+/// Suggests completions for the argument being typed, `partial`, given the
+/// arguments already typed before it.
+///
+/// If the last of `tokens` is a flag that takes a value (see
+/// `ArgumentType::takes_value`), the only suggestion is
+/// `Completion::File`. Otherwise every key from `ArgumentType::known_keys`
+/// that starts with `partial` is suggested, flags first. Keys are matched
+/// case-sensitively, just as the parser matches them.
+///
+/// # Examples
+///
+/// ```
+/// use adante::{complete, ArgumentType, Completion};
+///
+/// enum FlagType {
+///     Verbose,
+///     Out,
+/// }
+/// impl ArgumentType for FlagType {
+///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+///                                -> Result<Self, ErrorType> {
+///         match key {
+///             "-v" | "--verbose" => Ok(Self::Verbose),
+///             "-o" | "--out" => Ok(Self::Out),
+///             _ => Err(error),
+///         }
+///     }
+///     fn known_keys() -> &'static [&'static str] {
+///         &["-v", "--verbose", "-o", "--out"]
+///     }
+///     fn takes_value(&self) -> bool {
+///         matches!(self, Self::Out)
+///     }
+/// }
+/// enum ActionType {
+///     Add,
+/// }
+/// impl ArgumentType for ActionType {
+///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+///         -> Result<Self, ErrorType> {
+///         match key {
+///             "add" => Ok(Self::Add),
+///             _ => Err(error),
+///         }
+///     }
+///     fn known_keys() -> &'static [&'static str] {
+///         &["add"]
+///     }
+/// }
+///
+/// let completions = complete::<FlagType, ActionType>(&["add"], "--v");
+/// assert_eq!(completions, vec![Completion::Flag("--verbose")]);
+/// assert_eq!(complete::<FlagType, ActionType>(&["-o"], ""), vec![Completion::File]);
+/// ```
+pub fn complete<F: ArgumentType, A: ArgumentType>(tokens: &[&str], partial: &str) -> Vec<Completion> {
+    if let Some(last) = tokens.last() {
+        if let TokenKind::ShortFlag { key, value: None } | TokenKind::LongFlag { key, value: None } =
+            token::classify(last, &ParseOptions::new())
+        {
+            if matches!(F::from_str(key, ParseError::UnknownFlag), Ok(flag) if flag.takes_value()) {
+                return Vec::from([Completion::File]);
+            }
+        }
+    }
+    let matching = |key: &&&'static str| key.starts_with(partial);
+    let flags = F::known_keys().iter().filter(matching).map(|key| Completion::Flag(key));
+    let actions = A::known_keys().iter().filter(matching).map(|key| Completion::Action(key));
+    flags.chain(actions).collect()
+}
+
+impl<F: ArgumentType, A: ArgumentType> Arguments<F, A> {
+    /// Returns a bash script that completes the flag and action keys listed
+    /// by `ArgumentType::known_keys` for `program`. Source it, or save it
+    /// with something like `myprog --generate-completion > file`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use adante::{ArgumentType, Arguments};
+    ///
+    /// enum FlagType {
+    ///     Help,
+    /// }
+    /// impl ArgumentType for FlagType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "-h" | "--help" => Ok(Self::Help),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    ///     fn known_keys() -> &'static [&'static str] {
+    ///         &["-h", "--help"]
+    ///     }
+    /// }
+    /// enum ActionType {
+    ///     Add,
+    /// }
+    /// impl ArgumentType for ActionType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///         -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "add" => Ok(Self::Add),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    ///     fn known_keys() -> &'static [&'static str] {
+    ///         &["add"]
+    ///     }
+    /// }
+    ///
+    /// let script = Arguments::<FlagType, ActionType>::bash_completion("myprog");
+    /// assert!(script.contains("compgen -W \"-h --help add\""));
+    /// assert!(script.ends_with("complete -F _myprog myprog\n"));
+    /// ```
+    pub fn bash_completion(program: &str) -> String {
+        let function = completion_function(program);
+        format!(
+            "{function}() {{\n    \
+             local cur=\"${{COMP_WORDS[COMP_CWORD]}}\"\n    \
+             COMPREPLY=($(compgen -W \"{keys}\" -- \"$cur\"))\n\
+             }}\n\
+             complete -F {function} {program}\n",
+            function = function,
+            keys = Self::completion_keys().join(" "),
+            program = program,
+        )
+    }
+    /// Returns a zsh script that completes the flag and action keys listed
+    /// by `ArgumentType::known_keys` for `program`, like
+    /// `Arguments::bash_completion`.
+    pub fn zsh_completion(program: &str) -> String {
+        let function = completion_function(program);
+        format!(
+            "#compdef {program}\n\n\
+             {function}() {{\n    \
+             local -a keys\n    \
+             keys=({keys})\n    \
+             compadd -a keys\n\
+             }}\n\n\
+             compdef {function} {program}\n",
+            function = function,
+            keys = Self::completion_keys().join(" "),
+            program = program,
+        )
+    }
+    // Every flag key followed by every action key
+    fn completion_keys() -> Vec<&'static str> {
+        F::known_keys().iter().chain(A::known_keys()).copied().collect()
+    }
+}
+
+// Names the shell function after the program, keeping it a valid identifier
+fn completion_function(program: &str) -> String {
+    let name: String = program
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("_{}", name)
+}
@@ -0,0 +1,76 @@
+use crate::{Arguments, Error};
+
+/// A trait for types built from parsed arguments, such as a tool's config
+/// struct, used by `Arguments::into_struct`.
+///
+/// # Examples
+///
+/// ```
+/// use adante::{ArgumentType, Arguments, FromArguments, ParseError};
+///
+/// #[derive(Debug, Clone, Copy, PartialEq)]
+/// enum FlagType {
+///     Verbose,
+///     Out,
+/// }
+/// impl ArgumentType for FlagType {
+///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+///                                -> Result<Self, ErrorType> {
+///         match key {
+///             "-v" | "--verbose" => Ok(Self::Verbose),
+///             "-o" | "--out" => Ok(Self::Out),
+///             _ => Err(error),
+///         }
+///     }
+/// }
+/// #[derive(Debug, Clone, Copy, PartialEq)]
+/// enum ActionType {
+///     Build,
+/// }
+/// impl ArgumentType for ActionType {
+///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+///         -> Result<Self, ErrorType> {
+///         match key {
+///             "build" => Ok(Self::Build),
+///             _ => Err(error),
+///         }
+///     }
+/// }
+///
+/// struct Config {
+///     verbose: bool,
+///     out: String,
+/// }
+/// impl FromArguments<FlagType, ActionType> for Config {
+///     type Err = ParseError;
+///     fn from_arguments(args: &Arguments<FlagType, ActionType>) -> Result<Self, ParseError> {
+///         let out = args.last_value_of(FlagType::Out).ok_or(ParseError::MissingValue)?;
+///         Ok(Config {
+///             verbose: args.contains_flag(FlagType::Verbose),
+///             out: out.to_string(),
+///         })
+///     }
+/// }
+///
+/// let args: Arguments<FlagType, ActionType> =
+///     Arguments::parse(vec!["build", "--out=dist"], ParseError::UnknownFlag).unwrap();
+/// let config: Config = args.into_struct().unwrap();
+///
+/// assert!(!config.verbose);
+/// assert_eq!(config.out, "dist");
+/// ```
+pub trait FromArguments<F, A>: Sized {
+    /// The error returned when the arguments don't make a valid value
+    type Err: Error;
+    /// A user implemented function that builds the type from the parsed
+    /// arguments.
+    fn from_arguments(args: &Arguments<F, A>) -> Result<Self, Self::Err>;
+}
+
+impl<F, A> Arguments<F, A> {
+    /// Builds a `FromArguments` type, such as a config struct, from the
+    /// parsed arguments.
+    pub fn into_struct<T: FromArguments<F, A>>(&self) -> Result<T, T::Err> {
+        T::from_arguments(self)
+    }
+}
@@ -0,0 +1,353 @@
+//! The `Error` trait, implemented by the user's error type, along with the
+//! ready made `ParseError`.
+//!
+//! Both are re-exported at the crate root, so `adante::Error` and
+//! `adante::error::Error` name the same trait.
+//!
+//! ```
+//! fn report<E: adante::error::Error>(error: &E) -> String {
+//!     error.message().into_owned()
+//! }
+//!
+//! assert_eq!(report(&adante::ParseError::EmptyArg), "Empty argument");
+//! ```
+
+use crate::Messages;
+use alloc::borrow::Cow;
+use core::fmt;
+
+/// A trait that describes the functions an error must implement to be valid
+pub trait Error {
+    /// A user implemented function that performs a task then exits
+    /// depending on the type of error it is called on.
+    ///
+    /// In proper usage `std::process:exit(1)` would be used; however, this
+    /// example just uses `assert_eq!(2 + 2, 4)` to validate the test.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use adante::Error;
+    ///
+    /// #[derive(Debug, Clone, Copy)]
+    /// enum ErrorType {
+    ///     Syntax,
+    ///     InvalidAction,
+    ///     InvalidFlag,
+    ///     NoFlagVal,
+    /// }
+    /// impl Error for ErrorType {
+    ///     fn handle(&self) {
+    ///         // Handle code goes here:
+    ///         match self {
+    ///             Self::Syntax => assert_eq!(2 + 2, 4),           // Only branch that should work
+    ///             Self::InvalidAction => assert_eq!(2 + 2, 5),
+    ///             Self::InvalidFlag => assert_eq!(2 + 2, 5),
+    ///             Self::NoFlagVal => assert_eq!(2 + 2, 5),
+    ///         }
+    ///     }
+    ///     fn as_str(&self) -> &str {" "}
+    /// }
+    ///
+    /// let test_error = ErrorType::Syntax;
+    /// test_error.handle();
+    /// ```
+    fn handle(&self);
+    /// A user implemented function like `handle` that also receives the
+    /// argument that failed, or an empty string when the failure isn't tied
+    /// to a single argument. Defaults to calling `handle`.
+    ///
+    /// The parser calls this itself before returning an error when
+    /// `ParseOptions::handle_errors` is enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use adante::Error;
+    /// use std::cell::RefCell;
+    ///
+    /// struct ErrorType {
+    ///     seen: RefCell<String>,
+    /// }
+    /// impl Error for ErrorType {
+    ///     fn handle(&self) {  }
+    ///     fn handle_with(&self, token: &str) {
+    ///         // In proper usage this would print the token and exit
+    ///         self.seen.replace(token.to_string());
+    ///     }
+    ///     fn as_str(&self) -> &str {
+    ///         "Syntax Error"
+    ///     }
+    /// }
+    ///
+    /// let error = ErrorType { seen: RefCell::new(String::new()) };
+    /// error.handle_with("--frob");
+    /// assert_eq!(*error.seen.borrow(), "--frob");
+    /// ```
+    fn handle_with(&self, token: &str) {
+        let _ = token;
+        self.handle()
+    }
+    /// A user implemented function that returns a &str (usually an error message)
+    /// depending ont he type of error it is called on.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use adante::Error;
+    ///
+    /// #[derive(Debug, Clone, Copy)]
+    /// enum ErrorType {
+    ///     Syntax,
+    ///     InvalidAction,
+    ///     InvalidFlag,
+    ///     NoFlagVal,
+    /// }
+    /// impl Error for ErrorType {
+    ///     fn handle(&self) {  }
+    ///     fn as_str(&self) -> &str {
+    ///         match self {
+    ///             Self::Syntax => "Good!",
+    ///             Self::InvalidAction => "Bad!",
+    ///             Self::InvalidFlag => "Bad!",
+    ///             Self::NoFlagVal => "Bad!",
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let test_error = ErrorType::Syntax;
+    /// assert_eq!(test_error.as_str(), "Good!");
+    /// ```
+    fn as_str(&self) -> &str;
+    /// A user implemented function that returns the error message shown to
+    /// users, such as by the `Display` impl of `ParseFailure`. Defaults to
+    /// `as_str`.
+    ///
+    /// Override this when the message is built at runtime, for example to
+    /// include the offending argument.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use adante::Error;
+    /// use std::borrow::Cow;
+    ///
+    /// #[derive(Debug, Clone)]
+    /// enum ErrorType {
+    ///     Syntax,
+    ///     UnknownFlag(String),
+    /// }
+    /// impl Error for ErrorType {
+    ///     fn handle(&self) {  }
+    ///     fn as_str(&self) -> &str {
+    ///         match self {
+    ///             Self::Syntax => "Syntax Error",
+    ///             Self::UnknownFlag(_) => "Unknown flag",
+    ///         }
+    ///     }
+    ///     fn message(&self) -> Cow<'_, str> {
+    ///         match self {
+    ///             Self::UnknownFlag(flag) => Cow::Owned(format!("Unknown flag '{}'", flag)),
+    ///             _ => Cow::Borrowed(self.as_str()),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(ErrorType::Syntax.message(), "Syntax Error");
+    /// assert_eq!(ErrorType::UnknownFlag("-q".to_string()).message(), "Unknown flag '-q'");
+    /// ```
+    fn message(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self.as_str())
+    }
+    /// Returns the message in the language of the given catalog, as used
+    /// when rendering a `ParseFailure` from a parse with
+    /// `ParseOptions::messages`. Defaults to `message`; `ParseError` looks
+    /// its message up in the catalog.
+    fn localized(&self, messages: &Messages) -> Cow<'_, str> {
+        let _ = messages;
+        self.message()
+    }
+    /// A user implemented function that returns the error to report when a
+    /// flag that cannot take a value is given one (see
+    /// `ArgumentType::takes_value`). Defaults to a copy of the error itself.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use adante::Error;
+    ///
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum ErrorType {
+    ///     Syntax,
+    ///     FlagVal,
+    /// }
+    /// impl Error for ErrorType {
+    ///     fn handle(&self) {  }
+    ///     fn as_str(&self) -> &str {" "}
+    ///     fn unexpected_value(&self) -> Self {
+    ///         Self::FlagVal
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(ErrorType::Syntax.unexpected_value(), ErrorType::FlagVal);
+    /// ```
+    fn unexpected_value(&self) -> Self
+    where
+        Self: core::marker::Sized + Clone,
+    {
+        self.clone()
+    }
+    /// A user implemented function that returns the error to report when a
+    /// flag that takes a value is given none while
+    /// `ParseOptions::require_values` is set. Defaults to a copy of the
+    /// error itself.
+    fn missing_value(&self) -> Self
+    where
+        Self: core::marker::Sized + Clone,
+    {
+        self.clone()
+    }
+    /// A user implemented function that returns the error to pass to
+    /// `ArgumentType::from_str` when parsing a flag, and so the error
+    /// reported for an unknown flag. Defaults to a copy of the error itself.
+    fn unknown_flag(&self) -> Self
+    where
+        Self: core::marker::Sized + Clone,
+    {
+        self.clone()
+    }
+    /// A user implemented function that returns the error to pass to
+    /// `ArgumentType::from_str` when parsing an action, and so the error
+    /// reported for an unknown action. Defaults to a copy of the error
+    /// itself.
+    fn unknown_action(&self) -> Self
+    where
+        Self: core::marker::Sized + Clone,
+    {
+        self.clone()
+    }
+    /// A user implemented function that returns the error to report when
+    /// the number of positional arguments is outside the range set with
+    /// `ParseOptions::positionals`, given how many were found. Defaults to a
+    /// copy of the error itself.
+    fn positional_count(&self, found: usize) -> Self
+    where
+        Self: core::marker::Sized + Clone,
+    {
+        let _ = found;
+        self.clone()
+    }
+    /// A user implemented function that returns the error to report for an
+    /// empty argument. Defaults to a copy of the error itself.
+    fn empty_arg(&self) -> Self
+    where
+        Self: core::marker::Sized + Clone,
+    {
+        self.clone()
+    }
+}
+
+/// A ready made error type for tools that don't need their own.
+///
+/// Pass any variant to `Arguments::parse`; the parser picks the variant that
+/// describes what actually went wrong.
+///
+/// # Examples
+/// ```
+/// use adante::{ArgumentType, Arguments, ParseError};
+///
+/// #[derive(Debug, Clone, Copy, PartialEq)]
+/// enum FlagType {
+///     Verbose,
+/// }
+/// impl ArgumentType for FlagType {
+///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+///                                -> Result<Self, ErrorType> {
+///         match key {
+///             "-v" | "--verbose" => Ok(Self::Verbose),
+///             _ => Err(error),
+///         }
+///     }
+/// }
+/// #[derive(Debug, Clone, Copy, PartialEq)]
+/// enum ActionType {
+///     Add,
+/// }
+/// impl ArgumentType for ActionType {
+///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+///         -> Result<Self, ErrorType> {
+///         match key {
+///             "a" | "add" => Ok(Self::Add),
+///             _ => Err(error),
+///         }
+///     }
+/// }
+///
+/// let result = Arguments::<FlagType, ActionType>::parse(vec!["add", "-q"], ParseError::UnknownFlag);
+/// assert_eq!(result.unwrap_err(), ParseError::UnknownFlag);
+///
+/// let result = Arguments::<FlagType, ActionType>::parse(vec!["-v", "frob"], ParseError::UnknownFlag);
+/// assert_eq!(result.unwrap_err(), ParseError::UnknownAction);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParseError {
+    /// A flag that the flag type doesn't recognize
+    UnknownFlag,
+    /// An action that the action type doesn't recognize
+    UnknownAction,
+    /// A flag that needs a value was given none (see
+    /// `ParseOptions::require_values`)
+    MissingValue,
+    /// A flag that takes no value was given one
+    UnexpectedValue,
+    /// An empty argument
+    EmptyArg,
+}
+
+impl Error for ParseError {
+    /// Prints the error message to stderr and exits with code 1.
+    ///
+    /// Without the `std` feature, panics with the error message instead.
+    fn handle(&self) {
+        #[cfg(feature = "std")]
+        {
+            eprintln!("{}", self.message());
+            std::process::exit(1);
+        }
+        #[cfg(not(feature = "std"))]
+        panic!("{}", self.message());
+    }
+    fn as_str(&self) -> &str {
+        Messages::ENGLISH.parse_error(self)
+    }
+    fn localized(&self, messages: &Messages) -> Cow<'_, str> {
+        Cow::Borrowed(messages.parse_error(self))
+    }
+    fn unexpected_value(&self) -> Self {
+        Self::UnexpectedValue
+    }
+    fn missing_value(&self) -> Self {
+        Self::MissingValue
+    }
+    fn unknown_flag(&self) -> Self {
+        Self::UnknownFlag
+    }
+    fn unknown_action(&self) -> Self {
+        Self::UnknownAction
+    }
+    fn empty_arg(&self) -> Self {
+        Self::EmptyArg
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Lets `?` turn a `ParseError` into a `Box<dyn std::error::Error>`.
+///
+/// Requires the `std` feature.
+#[cfg(feature = "std")]
+impl std::error::Error for ParseError {}
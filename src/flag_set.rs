@@ -0,0 +1,123 @@
+use crate::{ArgumentType, Arguments};
+use core::fmt;
+use core::marker::PhantomData;
+
+/// The set of flag keys that were passed, as returned by
+/// `Arguments::flag_set`, stored as a 64 bit mask so that checking for a
+/// key doesn't scan the flags.
+///
+/// Each key is stored at the bit given by `ArgumentType::bit`.
+pub struct FlagSet<F> {
+    bits: u64,
+    keys: PhantomData<fn() -> F>,
+}
+
+impl<F: ArgumentType> FlagSet<F> {
+    /// A constructor for an empty FlagSet.
+    pub fn new() -> Self {
+        FlagSet {
+            bits: 0,
+            keys: PhantomData,
+        }
+    }
+    /// Adds a key, returning `false` if its bit doesn't fit in the set.
+    pub fn insert(&mut self, key: F) -> bool {
+        match 1u64.checked_shl(key.bit()) {
+            Some(bit) => {
+                self.bits |= bit;
+                true
+            }
+            None => false,
+        }
+    }
+    /// Returns whether the key is in the set.
+    pub fn contains(&self, key: F) -> bool {
+        matches!(1u64.checked_shl(key.bit()), Some(bit) if self.bits & bit != 0)
+    }
+    /// Returns whether the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+    /// Returns the keys that are in either set.
+    pub fn union(self, other: Self) -> Self {
+        FlagSet {
+            bits: self.bits | other.bits,
+            keys: PhantomData,
+        }
+    }
+    /// Returns the keys that are in both sets.
+    pub fn intersection(self, other: Self) -> Self {
+        FlagSet {
+            bits: self.bits & other.bits,
+            keys: PhantomData,
+        }
+    }
+}
+
+impl<F: ArgumentType> Default for FlagSet<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F> Clone for FlagSet<F> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<F> Copy for FlagSet<F> {}
+
+impl<F> PartialEq for FlagSet<F> {
+    fn eq(&self, other: &Self) -> bool {
+        self.bits == other.bits
+    }
+}
+
+impl<F> Eq for FlagSet<F> {}
+
+impl<F> fmt::Debug for FlagSet<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "FlagSet({:#b})", self.bits)
+    }
+}
+
+impl<F: ArgumentType + Clone, A, V> Arguments<F, A, V> {
+    /// Collects the keys of the flags passed into a `FlagSet`, for checking
+    /// them in constant time.
+    ///
+    /// Returns `None` if a key's `ArgumentType::bit` is 64 or more, since
+    /// the set can't hold it. This is always the case for flag types that
+    /// don't implement `bit`, while `arg_enum!` and the derive macro number
+    /// the variants in order, so any enum of up to 64 flags fits.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use adante::{arg_enum, Arguments, ParseError};
+    ///
+    /// arg_enum! {
+    ///     #[derive(Debug, Clone, Copy, PartialEq)]
+    ///     pub enum FlagType {
+    ///         Help => ["-h", "--help"],
+    ///         Verbose => ["-v", "--verbose"],
+    ///     }
+    /// }
+    ///
+    /// let args: Arguments<FlagType, FlagType> =
+    ///     Arguments::parse(vec!["-v"], ParseError::UnknownFlag).unwrap();
+    /// let flags = args.flag_set().unwrap();
+    ///
+    /// assert!(flags.contains(FlagType::Verbose));
+    /// assert!(!flags.contains(FlagType::Help));
+    /// ```
+    pub fn flag_set(&self) -> Option<FlagSet<F>> {
+        let mut set = FlagSet::new();
+        for flag in &self.flags {
+            if !set.insert(flag.key.clone()) {
+                return None;
+            }
+        }
+        Some(set)
+    }
+}
@@ -117,158 +117,534 @@
 //! Now your parser is complete! By plugging `std::env::args::collect()` into
 //! `Arguments::parse()`, you will get a working `Arguments` object!
 //!
+//! ## `no_std`
+//!
+//! The parser itself only needs `alloc`. Disabling the default `std` feature
+//! builds the crate as `#![no_std]`, leaving out the helpers that need the
+//! standard library, such as `Arguments::fill_from_env`.
+//!
+//! ## `derive`
+//!
+//! Enabling the `derive` feature lets `#[derive(ArgumentType)]` write the
+//! impl above, given the keys of each variant:
+//!
+//! ```ignore
+//! #[derive(Debug, Clone, Copy, PartialEq, adante::ArgumentType)]
+//! enum ActionType {
+//!     #[key("a", "add")]
+//!     Add,
+//!     #[key("r", "remove")]
+//!     Remove,
+//! }
+//! ```
+//!
+//! ## `color`
+//!
+//! Enabling the `color` feature, which needs `std`, colors the text from
+//! `ParseFailure::render` and `Arguments::usage` with ANSI escapes when it's
+//! headed for a terminal and `NO_COLOR` isn't set. `ColorChoice` overrides
+//! the check through `render_with` and `usage_with`.
+//!
+//! ## `serde`
+//!
+//! Enabling the `serde` feature implements `Serialize` and `Deserialize` for
+//! `Flag` and `Arguments` whenever your flag and action types implement them.
+//!
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 
+#[cfg(test)]
+mod no_std_tests;
 #[cfg(test)]
 mod tests;
 
-/// A trait describing the shared methods of both Flags and Arguments
+pub mod arg_type;
+pub mod check;
+mod color;
+pub mod common;
+mod completion;
+mod convert;
+pub mod error;
+mod flag_set;
+mod macros;
+mod messages;
+mod options;
+mod parser;
+mod recognize;
+mod split;
+pub mod token;
+mod validate;
+
+pub use arg_type::{ArgumentType, KeyStyle, ParseKey};
+#[cfg(feature = "color")]
+pub use color::ColorChoice;
+pub use completion::{complete, Completion};
+pub use convert::FromArguments;
+#[cfg(feature = "derive")]
+pub use adante_derive::ArgumentType;
+#[doc(hidden)]
+pub use macros::__private;
+pub use error::{Error, ParseError};
+pub use flag_set::FlagSet;
+pub use messages::Messages;
+pub use options::{ActionPolicy, DuplicatePolicy, ParseOptions, PositionalPolicy};
+pub use parser::{Items, ParseIter, ParsedItem, ParsedRef, Parser};
+pub use split::{split, SplitError};
+pub use validate::{Rule, ValidationReport};
+
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::mem;
+use core::ops::Range;
+use core::str::FromStr;
+use recognize::{convert_flag, Item, Recognizer};
+use token::TokenKind;
+
+/// A subset struct of the `Arguments` struct that describes a Flag object
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Flag<T, V = String> {
+    pub key: T,
+    // NOTE: The value is a String by default,
+    // a &str borrowed from the input when
+    // parsed with `Arguments::parse_borrowed`,
+    // or a Cow with `Arguments::parse_cow`.
+    pub value: Option<V>,
+    /// The arguments consumed after the flag when
+    /// `ParseOptions::greedy_values` is enabled
+    #[cfg_attr(feature = "serde", serde(default = "Vec::new"))]
+    pub values: Vec<V>,
+    /// Whether the flag was written as `-x` or `--xxx`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub style: FlagStyle,
+}
+
+impl<T: ArgumentType, V: AsRef<str>> Flag<T, V> {
+    /// Writes the flag back out as a single argument using its
+    /// `ArgumentType::canonical_key`, such as `--verbose` or `--out=path`.
+    /// Greedy `values` aren't included.
+    pub fn render(&self) -> String {
+        match &self.value {
+            Some(value) => format!("{}={}", self.key.canonical_key(), value.as_ref()),
+            None => self.key.canonical_key().to_string(),
+        }
+    }
+}
 
-pub trait ArgumentType {
-    /// A user implemented function that takes a string as input and returns an
-    /// argument type.
+impl<T: ArgumentType + fmt::Debug, V: fmt::Debug> Flag<T, V> {
+    /// Formats the flag like its `Debug` impl, but with each value shown
+    /// as `"***"` if the key is `ArgumentType::sensitive`, so it can be
+    /// logged safely.
     ///
     /// # Examples
-    /// ```
-    /// use adante::{ArgumentType, Error};
-    ///
-    /// #[derive(Debug, Clone, Copy)]
-    /// enum ErrorType {
-    ///     Syntax, // EXTREMELY simple example
-    ///             // More complex examples are shown in
-    ///             // the documentation for Error
-    /// }
-    /// impl Error for ErrorType {
-    ///     fn handle(&self) {
-    ///         ()
-    ///     }
-    ///     fn as_str(&self) -> &str {
-    ///         "Syntax Error"
-    ///     }
-    /// }
     ///
+    /// ```
+    /// use adante::{ArgumentType, Flag, FlagStyle};
     ///
     /// #[derive(Debug, Clone, Copy, PartialEq)]
     /// enum FlagType {
-    ///     Help,
-    ///     Verbose,
-    ///     Print,
-    ///     TestFail, // NOTE: For testing only
-    ///               // Use Error
+    ///     Token,
     /// }
     /// impl ArgumentType for FlagType {
     ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
     ///                                -> Result<Self, ErrorType> {
     ///         match key {
-    ///             "-h" | "--help" => Ok(Self::Help),
-    ///             "-v" | "--verbose" => Ok(Self::Verbose),
-    ///             "-p" | "--print" => Ok(Self::Print),
+    ///             "--token" => Ok(Self::Token),
     ///             _ => Err(error),
     ///         }
     ///     }
+    ///     fn sensitive(&self) -> bool {
+    ///         true
+    ///     }
     /// }
-    /// let result = match FlagType::from_str("-v", ErrorType::Syntax) {
-    ///     Ok(t) => t,
-    ///     Err(_) => FlagType::TestFail // In actual usecase this would pipe into
-    ///                                  // An Error's handle function
-    /// };
-    /// assert_eq!(result, FlagType::Verbose)
     ///
+    /// let flag = Flag {
+    ///     key: FlagType::Token,
+    ///     value: Some("abc123".to_string()),
+    ///     values: Vec::new(),
+    ///     style: FlagStyle::Long,
+    /// };
+    /// assert_eq!(
+    ///     flag.redacted_debug(),
+    ///     r#"Flag { key: Token, value: Some("***"), values: [], style: Long }"#
+    /// );
     /// ```
-    fn from_str<E: Error>(key: &str, error: E) -> Result<Self, E>
-    where
-        Self: std::marker::Sized;
+    pub fn redacted_debug(&self) -> String {
+        format!("{:?}", Redacted(self))
+    }
 }
 
-/// A trait that describes the functions an error must implement to be valid
+// Formats a flag like its derived `Debug` impl, masking sensitive values
+struct Redacted<'a, T, V>(&'a Flag<T, V>);
 
-pub trait Error {
-    /// A user implemented function that performs a task then exits
-    /// depending on the type of error it is called on.
-    ///
-    /// In proper usage `std::process:exit(1)` would be used; however, this
-    /// example just uses `assert_eq!(2 + 2, 4)` to validate the test.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use adante::Error;
-    ///
-    /// #[derive(Debug, Clone, Copy)]
-    /// enum ErrorType {
-    ///     Syntax,
-    ///     InvalidAction,
-    ///     InvalidFlag,
-    ///     NoFlagVal,
-    /// }
-    /// impl Error for ErrorType {
-    ///     fn handle(&self) {
-    ///         // Handle code goes here:
-    ///         match self {
-    ///             Self::Syntax => assert_eq!(2 + 2, 4),           // Only branch that should work
-    ///             Self::InvalidAction => assert_eq!(2 + 2, 5),
-    ///             Self::InvalidFlag => assert_eq!(2 + 2, 5),
-    ///             Self::NoFlagVal => assert_eq!(2 + 2, 5),
-    ///         }
-    ///     }
-    ///     fn as_str(&self) -> &str {" "}
-    /// }
+impl<T: ArgumentType + fmt::Debug, V: fmt::Debug> fmt::Debug for Redacted<'_, T, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let flag = self.0;
+        let mut debug = f.debug_struct("Flag");
+        debug.field("key", &flag.key);
+        if flag.key.sensitive() {
+            let mask = "***";
+            debug.field("value", &flag.value.as_ref().map(|_| mask));
+            debug.field("values", &flag.values.iter().map(|_| mask).collect::<Vec<_>>());
+        } else {
+            debug.field("value", &flag.value);
+            debug.field("values", &flag.values);
+        }
+        debug.field("style", &flag.style).finish()
+    }
+}
+
+/// The form a flag was written in, recorded on each parsed `Flag`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FlagStyle {
+    /// A single dash, such as `-v`
+    Short,
+    /// Two dashes, such as `--verbose`. Flags that weren't typed at all,
+    /// such as those added by `Arguments::fill_from_env`, are also long.
+    #[default]
+    Long,
+}
+
+/// Whether a flag was passed and with what value, as returned by
+/// `Arguments::get`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlagMatch<'a> {
+    /// The flag wasn't passed
+    Absent,
+    /// The flag was passed, but never with a value
+    Present,
+    /// The flag was passed with a value; the last one if there are several
+    Value(&'a str),
+}
+
+/// A warning about an argument that parsed but shouldn't be relied on,
+/// such as a deprecated flag, passed to the hook set with
+/// `ParseOptions::on_warning`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    /// The argument the warning is about
+    pub token: String,
+    /// The index of the argument in the parsed list
+    pub index: usize,
+    /// What's wrong with it, such as the message from
+    /// `ArgumentType::deprecated`
+    pub message: &'static str,
+}
+
+/// The result of `Arguments::parse_with_warnings`: the parsed arguments
+/// along with any warnings about them.
+#[derive(Debug, PartialEq)]
+pub struct ParseOutcome<F, A> {
+    /// The parsed arguments
+    pub args: Arguments<F, A>,
+    /// A warning for each argument that parsed but shouldn't be relied on,
+    /// in the order they were passed
+    pub warnings: Vec<Warning>,
+}
+
+/// An error returned by `Arguments::parse_with`, pairing the user's error
+/// with the position of the offending argument.
+#[derive(Debug, Clone)]
+pub struct ParseFailure<E> {
+    /// The user defined error
+    pub error: E,
+    /// The index of the offending argument in the parsed list
+    pub index: usize,
+    /// The offending argument itself
+    pub token: String,
+    /// The byte range of the offending argument in the parsed list joined
+    /// with spaces, as by `env_args.join(" ")`, for pointing at it with
+    /// `render_caret`. Empty at the end of the list if an argument is
+    /// missing there, and `0..0` if the failure wasn't built by a parse.
+    pub span: Range<usize>,
+    /// The long keys an ambiguous abbreviation could stand for, when
+    /// `ParseOptions::allow_abbreviation` is set and that's why the
+    /// argument failed, which `Display` and `render` suggest. Empty
+    /// otherwise.
+    pub candidates: Vec<&'static str>,
+    /// The 1-based line of the offending argument, for failures from
+    /// `Arguments::from_config_str`, in which case `Display` and `render`
+    /// show it instead of the index. None otherwise.
+    pub line: Option<usize>,
+    // The catalog the failure is rendered with
+    messages: &'static Messages,
+}
+
+impl<E> ParseFailure<E> {
+    /// A constructor for the ParseFailure type.
+    pub fn new(error: E, index: usize, token: &str) -> Self {
+        ParseFailure {
+            error,
+            index,
+            token: token.to_string(),
+            span: 0..0,
+            candidates: Vec::new(),
+            line: None,
+            messages: &Messages::ENGLISH,
+        }
+    }
+    /// Sets the catalog that `render` and `Display` take their text from,
+    /// as a parse does from `ParseOptions::messages`.
+    pub fn with_messages(mut self, messages: &'static Messages) -> Self {
+        self.messages = messages;
+        self
+    }
+    /// Returns `joined`, the parsed list joined with spaces, with a second
+    /// line of carets under the offending argument, like:
     ///
-    /// let test_error = ErrorType::Syntax;
-    /// test_error.handle();
+    /// ```text
+    /// add -q
+    ///     ^^
     /// ```
-    fn handle(&self);
-    /// A user implemented function that returns a &str (usually an error message)
-    /// depending ont he type of error it is called on.
     ///
-    /// # Examples
-    ///
-    /// ```
-    /// use adante::Error;
+    /// The carets are placed by counting characters rather than bytes, so
+    /// non-ASCII text earlier on the line doesn't shift them, though
+    /// characters drawn two columns wide still will.
+    pub fn render_caret(&self, joined: &str) -> String {
+        let start = self.span.start.min(joined.len());
+        let end = self.span.end.clamp(start, joined.len());
+        let column = joined.get(..start).map_or(start, |before| before.chars().count());
+        let width = joined.get(start..end).map_or(1, |span| span.chars().count()).max(1);
+        format!("{}\n{}{}", joined, " ".repeat(column), "^".repeat(width))
+    }
+    // Lists the keys the token could stand for if it's an ambiguous
+    // abbreviation of a flag
+    pub(crate) fn with_candidates<F: ArgumentType>(mut self, options: &ParseOptions) -> Self {
+        if let TokenKind::LongFlag { key, value } = token::classify(&self.token, options) {
+            if lookup_exact::<F, _>(key, value, ParseError::UnknownFlag, options).is_err() {
+                let candidates = abbreviation_candidates::<F>(key, value, options);
+                if candidates.len() > 1 {
+                    self.candidates = candidates;
+                }
+            }
+        }
+        self
+    }
+    // Returns where the failure is shown to be: the line if it has one,
+    // otherwise the argument index
+    fn position(&self) -> (&'static str, usize) {
+        match self.line {
+            Some(line) => (self.messages.line, line),
+            None => (self.messages.argument, self.index),
+        }
+    }
+    // Lists the candidates, if there are any, as `; did you mean --verbose,
+    // --version?`, with each key yellow if color is enabled
+    fn suggestion(&self, color: bool) -> String {
+        if self.candidates.is_empty() {
+            return String::new();
+        }
+        let keys: Vec<String> =
+            self.candidates.iter().map(|key| color::paint(key, color::YELLOW, color)).collect();
+        format!("; {} {}?", self.messages.did_you_mean, keys.join(", "))
+    }
+    // Sets the span from the index, given the list that was parsed
+    pub(crate) fn locate(mut self, env_args: &[&str]) -> Self {
+        let start = env_args.iter().take(self.index).map(|arg| arg.len() + 1).sum();
+        self.span = match env_args.get(self.index) {
+            Some(arg) => start..start + arg.len(),
+            None => start.saturating_sub(1)..start.saturating_sub(1),
+        };
+        self
+    }
+}
+
+impl<E: Error> ParseFailure<E> {
+    /// Returns the line to print for the failure: `error: ` followed by its
+    /// `Display` text.
     ///
-    /// #[derive(Debug, Clone, Copy)]
-    /// enum ErrorType {
-    ///     Syntax,
-    ///     InvalidAction,
-    ///     InvalidFlag,
-    ///     NoFlagVal,
-    /// }
-    /// impl Error for ErrorType {
-    ///     fn handle(&self) {  }
-    ///     fn as_str(&self) -> &str {
-    ///         match self {
-    ///             Self::Syntax => "Good!",
-    ///             Self::InvalidAction => "Bad!",
-    ///             Self::InvalidFlag => "Bad!",
-    ///             Self::NoFlagVal => "Bad!",
-    ///         }
-    ///     }
-    /// }
+    /// With the `color` feature, the message is red, the argument bold and
+    /// the `candidates` yellow when stderr is a terminal and `NO_COLOR`
+    /// isn't set.
+    pub fn render(&self) -> String {
+        self.render_painted(color::stderr_colored())
+    }
+    /// Renders like `render`, but colored as the given choice says.
     ///
-    /// let test_error = ErrorType::Syntax;
-    /// assert_eq!(test_error.as_str(), "Good!");
-    /// ```
-    fn as_str(&self) -> &str;
+    /// Requires the `color` feature.
+    #[cfg(feature = "color")]
+    pub fn render_with(&self, choice: ColorChoice) -> String {
+        use std::io::IsTerminal;
+        self.render_painted(choice.resolve(std::io::stderr().is_terminal()))
+    }
+    // Renders the line, colored if enabled
+    fn render_painted(&self, color: bool) -> String {
+        let (position, n) = self.position();
+        format!(
+            "{}{} ({} {}: '{}'){}",
+            self.messages.error_prefix,
+            color::paint(&self.error.localized(self.messages), color::RED, color),
+            position,
+            n,
+            color::paint(&self.token, color::BOLD, color),
+            self.suggestion(color)
+        )
+    }
 }
 
-/// A subset struct of the `Arguments` struct that describes a Flag object
-
-#[derive(Debug)]
-pub struct Flag<T: ArgumentType> {
-    pub key: T,
-    // NOTE: Thought making String generic here
-    // may have been overdoing it a bit.
-    // Consider.
-    pub value: Option<String>,
+impl<E: Error> fmt::Display for ParseFailure<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = self.error.localized(self.messages);
+        let (position, n) = self.position();
+        write!(f, "{} ({} {}: '{}'){}", message, position, n, self.token, self.suggestion(false))
+    }
 }
 
-/// The meat of the library, describes an `Argument` object and its methods
+/// Lets `?` turn a failure from `Arguments::parse_with` into a
+/// `Box<dyn std::error::Error>`, for any user error that implements `Debug`.
+///
+/// Requires the `std` feature.
+///
+/// # Examples
+///
+/// ```
+/// use adante::{ArgumentType, Arguments, ParseError, ParseOptions};
+///
+/// #[derive(Debug, Clone, Copy, PartialEq)]
+/// enum FlagType {
+///     Verbose,
+/// }
+/// impl ArgumentType for FlagType {
+///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+///                                -> Result<Self, ErrorType> {
+///         match key {
+///             "-v" | "--verbose" => Ok(Self::Verbose),
+///             _ => Err(error),
+///         }
+///     }
+/// }
+///
+/// fn run(env_args: Vec<&str>) -> Result<usize, Box<dyn std::error::Error>> {
+///     let options = ParseOptions::new();
+///     let args: Arguments<FlagType, FlagType> =
+///         Arguments::parse_with(env_args, ParseError::UnknownFlag, &options)?;
+///     Ok(args.flags.len())
+/// }
+///
+/// assert_eq!(run(vec!["-v"]).unwrap(), 1);
+/// assert_eq!(run(vec!["-q"]).unwrap_err().to_string(), "Unknown flag (argument 0: '-q')");
+/// ```
+#[cfg(feature = "std")]
+impl<E: Error + fmt::Debug> std::error::Error for ParseFailure<E> {}
 
-#[derive(Debug)]
-pub struct Arguments<F: ArgumentType, A: ArgumentType> {
+/// The meat of the library, describes an `Argument` object and its methods
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Arguments<F, A, V = String> {
     /// A list of the user defined Flag types and optional values
-    pub flags: Vec<Flag<F>>,
+    pub flags: Vec<Flag<F, V>>,
     /// A list of the user defined Action types
     pub actions: Vec<A>,
+    /// The arguments that are neither flags nor actions, collected when
+    /// `ParseOptions::positionals` is set
+    #[cfg_attr(feature = "serde", serde(default = "Vec::new"))]
+    pub positionals: Vec<V>,
+    /// The `key=value` pairs after `--`, collected when
+    /// `ParseOptions::passthrough_pairs` is set, for handing to another tool
+    #[cfg_attr(feature = "serde", serde(default = "Vec::new"))]
+    pub passthrough: Vec<(String, String)>,
+    // The number of flags passed before each action, serialized so that
+    // global_flags and flags_after_action survive a round trip
+    #[cfg_attr(feature = "serde", serde(default = "Vec::new"))]
+    action_offsets: Vec<usize>,
+}
+
+impl<F: fmt::Debug, A: fmt::Debug, V: fmt::Debug> fmt::Debug for Arguments<F, A, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Arguments")
+            .field("flags", &self.flags)
+            .field("actions", &self.actions)
+            .field("positionals", &self.positionals)
+            .field("passthrough", &self.passthrough)
+            .finish()
+    }
+}
+
+/// Compares the flags, actions and positionals, but not how the flags and
+/// actions were interleaved.
+impl<F: PartialEq, A: PartialEq, V: PartialEq> PartialEq for Arguments<F, A, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.flags == other.flags
+            && self.actions == other.actions
+            && self.positionals == other.positionals
+            && self.passthrough == other.passthrough
+    }
+}
+
+/// `Arguments` for small tools with one enum covering both flags and
+/// actions.
+///
+/// Keys starting with `-` become flags and the rest become actions, so a
+/// single `from_str` can list both.
+///
+/// # Examples
+///
+/// ```
+/// use adante::{ArgumentType, ParseError, SingleArguments};
+///
+/// #[derive(Debug, Clone, Copy, PartialEq)]
+/// enum Command {
+///     Verbose,
+///     Add,
+/// }
+/// impl ArgumentType for Command {
+///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+///                                -> Result<Self, ErrorType> {
+///         match key {
+///             "-v" | "--verbose" => Ok(Self::Verbose),
+///             "add" => Ok(Self::Add),
+///             _ => Err(error),
+///         }
+///     }
+/// }
+///
+/// let args = SingleArguments::<Command>::parse(vec!["-v", "add"], ParseError::UnknownFlag).unwrap();
+/// assert_eq!(args.flags[0].key, Command::Verbose);
+/// assert_eq!(args.actions, vec![Command::Add]);
+/// ```
+pub type SingleArguments<T, V = String> = Arguments<T, T, V>;
+
+/// The result of `Arguments::parse_subcommands`, splitting the arguments
+/// around the first action.
+#[derive(Debug)]
+pub struct SubcommandArgs<F, A, S> {
+    /// The flags given before the first action
+    pub global: Vec<Flag<F>>,
+    /// The first action, if there was one
+    pub action: Option<A>,
+    /// What the dispatch closure made of the arguments after the action
+    pub sub: Option<S>,
+}
+
+impl<F, A, V> Default for Arguments<F, A, V> {
+    fn default() -> Self {
+        Arguments {
+            flags: Vec::new(),
+            actions: Vec::new(),
+            positionals: Vec::new(),
+            passthrough: Vec::new(),
+            action_offsets: Vec::new(),
+        }
+    }
+}
+
+/// Returns the value of a flag, as found by `Arguments::last_value_of`.
+///
+/// # Panics
+///
+/// Panics if the flag wasn't passed with a value. Use `last_value_of` or
+/// `get` to handle that case instead.
+impl<F: PartialEq, A, V: AsRef<str>> core::ops::Index<F> for Arguments<F, A, V> {
+    type Output = str;
+
+    fn index(&self, key: F) -> &str {
+        self.last_value_of(key).expect("flag was not passed with a value")
+    }
 }
 
 impl<F: ArgumentType, A: ArgumentType> Arguments<F, A> {
@@ -323,10 +699,16 @@ impl<F: ArgumentType, A: ArgumentType> Arguments<F, A> {
         Arguments {
             flags: Vec::new(),
             actions: Vec::new(),
+            positionals: Vec::new(),
+            passthrough: Vec::new(),
+            action_offsets: Vec::new(),
         }
     }
     /// The parsing function that returns a full Arguments object.
     ///
+    /// The error only needs to be `Clone`, so it can carry data such as a
+    /// `String` message.
+    ///
     /// More complicated usages and tests can be found in the tests.rs file.
     ///
     /// # Examples
@@ -402,51 +784,1942 @@ impl<F: ArgumentType, A: ArgumentType> Arguments<F, A> {
     /// assert_eq!(result, FlagType::Verbose);
     ///
     /// ```
-    pub fn parse<E: Error + Clone + Copy>(env_args: Vec<&str>, error: E) -> Result<Arguments<F, A>, E> {
-        let mut args = Arguments::new();
-        let mut eq_pos: usize = 0;
-        for arg in env_args.iter() {
-            // Detect if argument is option or action:
-            if &arg[0..1] == "-" {
-                // Assume flag, find seperator:
-                for (i, &byte) in arg.as_bytes().iter().enumerate() {
-                    if byte == b'=' {
-                        eq_pos = i;
-                    }
-                }
-                // Assume no value if no =:
-                if eq_pos == 0 {
-                    args.flags.push(Flag {
-                        key: match F::from_str(arg, error.clone()) {
-                            Ok(v) => v,
-                            Err(e) => return Err(e),
-                        },
-                        value: None,
-                    })
-                // Seperator found
-                // FIXME: BREAKS HERE
-                } else {
-                    let key = &arg[0..eq_pos];
-                    let val = &arg[(eq_pos + 1)..];
-                    args.flags.push(Flag {
-                        key: match F::from_str(key, error.clone()) {
-                            Ok(v) => v,
-                            Err(e) => return Err(e),
-                        },
-                        // TODO: make value field a &str by default
-                        value: Some(val.to_string()),
-                    })
-                }
-            // TODO: Recognize file path, omit or save to output
-            } else {
-                // Assume action, match string to type
-                args.actions.push(match A::from_str(arg, error) {
-                    Ok(v) => v,
-                    Err(e) => return Err(e),
-                })
-            }
-        }
-
-        Ok(args)
+    pub fn parse<E: Error + Clone>(env_args: Vec<&str>, error: E) -> Result<Arguments<F, A>, E> {
+        Self::parse_owned(env_args, error, &ParseOptions::new(), None)
+            .map_err(|failure| failure.error)
+    }
+    /// Parses like `Arguments::parse`, but also collects a `Warning` for
+    /// each deprecated flag (see `ArgumentType::deprecated`). Such flags are
+    /// still parsed as usual.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use adante::{ArgumentType, Arguments, ParseError};
+    ///
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum FlagType {
+    ///     Color,
+    ///     Colour,
+    /// }
+    /// impl ArgumentType for FlagType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "--color" => Ok(Self::Color),
+    ///             "--colour" => Ok(Self::Colour),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    ///     fn deprecated(&self) -> Option<&'static str> {
+    ///         match self {
+    ///             Self::Colour => Some("use --color instead"),
+    ///             Self::Color => None,
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let outcome = Arguments::<FlagType, FlagType>::parse_with_warnings(
+    ///     vec!["--colour=never"],
+    ///     ParseError::UnknownFlag,
+    /// )
+    /// .unwrap();
+    ///
+    /// assert_eq!(outcome.args.flags[0].key, FlagType::Colour);
+    /// assert_eq!(outcome.warnings[0].message, "use --color instead");
+    /// ```
+    pub fn parse_with_warnings<E: Error + Clone>(
+        env_args: Vec<&str>,
+        error: E,
+    ) -> Result<ParseOutcome<F, A>, E> {
+        let mut warnings = Vec::new();
+        let options = ParseOptions::new();
+        let args = Self::parse_collecting(env_args, error, &options, None, &mut warnings)
+            .map_err(|failure| failure.error)?;
+        Ok(ParseOutcome { args, warnings })
+    }
+    /// Parses like `Arguments::parse`, also returning how long the parse
+    /// took, for profiling tools that pass very long argument lists.
+    ///
+    /// Requires the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn parse_timed<E: Error + Clone>(
+        env_args: Vec<&str>,
+        error: E,
+    ) -> Result<(Arguments<F, A>, std::time::Duration), E> {
+        let start = std::time::Instant::now();
+        let args = Self::parse(env_args, error)?;
+        Ok((args, start.elapsed()))
+    }
+    /// Parses like `Arguments::parse`, but builds the error lazily: instead of
+    /// an error value, it takes a closure that is only called if an argument
+    /// fails, with that argument, so the error can carry the offending
+    /// token. The closure is called at most once, and gets an empty string
+    /// when the failure isn't tied to a single argument.
+    ///
+    /// The error hooks such as `Error::unknown_flag` don't apply here, and
+    /// the error type doesn't need to implement `Error` or `Clone`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use adante::{ArgumentType, Arguments};
+    ///
+    /// #[derive(Debug, PartialEq)]
+    /// enum ErrorType {
+    ///     Unknown(String),
+    /// }
+    ///
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum FlagType {
+    ///     Verbose,
+    /// }
+    /// impl ArgumentType for FlagType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "-v" | "--verbose" => Ok(Self::Verbose),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    /// }
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum ActionType {
+    ///     Add,
+    /// }
+    /// impl ArgumentType for ActionType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///         -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "a" | "add" => Ok(Self::Add),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let result: Result<Arguments<FlagType, ActionType>, _> =
+    ///     Arguments::parse_with_err(vec!["-v", "--bogus"], |token| ErrorType::Unknown(token.to_string()));
+    ///
+    /// assert_eq!(result.unwrap_err(), ErrorType::Unknown("--bogus".to_string()));
+    /// ```
+    pub fn parse_with_err<E, M>(env_args: Vec<&str>, make_error: M) -> Result<Arguments<F, A>, E>
+    where
+        M: Fn(&str) -> E,
+    {
+        Self::parse_owned(env_args, Deferred, &ParseOptions::new(), None)
+            .map_err(|failure| make_error(&failure.token))
+    }
+    /// Parses a whole command line, such as one typed into a REPL, by
+    /// breaking it into words with `split` and passing them to
+    /// `Arguments::parse`. A line that can't be split, such as one with an
+    /// unterminated quote, returns the error as well.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use adante::{ArgumentType, Arguments, ParseError};
+    ///
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum FlagType {
+    ///     Verbose,
+    ///     Message,
+    /// }
+    /// impl ArgumentType for FlagType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "-v" | "--verbose" => Ok(Self::Verbose),
+    ///             "-m" | "--msg" => Ok(Self::Message),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    /// }
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum ActionType {
+    ///     Add,
+    /// }
+    /// impl ArgumentType for ActionType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///         -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "a" | "add" => Ok(Self::Add),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let args: Arguments<FlagType, ActionType> =
+    ///     Arguments::parse_str("add -v --msg='hi there'", ParseError::UnknownFlag).unwrap();
+    ///
+    /// assert_eq!(args.flags[1].value, Some("hi there".to_string()));
+    /// assert_eq!(args.actions, vec![ActionType::Add]);
+    /// ```
+    pub fn parse_str<E: Error + Clone>(line: &str, error: E) -> Result<Arguments<F, A>, E> {
+        let words = match split(line) {
+            Ok(words) => words,
+            Err(_) => return Err(error),
+        };
+        Self::parse(words.iter().map(String::as_str).collect(), error)
+    }
+    /// The same as `Arguments::parse_str`.
+    pub fn parse_line<E: Error + Clone>(line: &str, error: E) -> Result<Arguments<F, A>, E> {
+        Self::parse_str(line, error)
+    }
+    /// Parses the raw arguments from `std::env::args_os`, such as
+    /// `std::env::args_os().skip(1).collect()`, like `Arguments::parse`. An
+    /// argument that isn't valid UTF-8 returns the error as well.
+    ///
+    /// Requires the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn parse_os<E: Error + Clone>(
+        env_args: Vec<std::ffi::OsString>,
+        error: E,
+    ) -> Result<Arguments<F, A>, E> {
+        let mut args = Vec::with_capacity(env_args.len());
+        for arg in &env_args {
+            match arg.to_str() {
+                Some(arg) => args.push(arg),
+                None => return Err(error),
+            }
+        }
+        Self::parse(args, error)
+    }
+    /// Parses like `Arguments::parse`, but with the behavior tweaked by
+    /// `options`, and with errors wrapped in a `ParseFailure` that records
+    /// which argument failed.
+    ///
+    /// Duplicate flags are always allowed here; see
+    /// `Arguments::parse_with_dedup`.
+    pub fn parse_with<E: Error + Clone>(
+        env_args: Vec<&str>,
+        error: E,
+        options: &ParseOptions,
+    ) -> Result<Arguments<F, A>, ParseFailure<E>> {
+        Self::parse_owned(env_args, error, options, None)
+    }
+    /// Parses a tiny configuration format, such as the contents of a
+    /// `~/.mytoolrc`, into an `Arguments` object.
+    ///
+    /// Each line holds one argument, written just as it would be on the
+    /// command line: flags keep their dashes (`--verbose`, `--out=path`) and
+    /// anything else is an action. Whitespace around a line and around its
+    /// first `=` is ignored, as are blank lines and lines starting with `#`.
+    ///
+    /// On failure, the returned `ParseFailure` has the 1-based number of the
+    /// offending line as its `line` and the line itself as its `token`,
+    /// while its `index` counts only the lines that aren't blank or
+    /// comments.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use adante::{ArgumentType, Arguments, Error};
+    ///
+    /// #[derive(Debug, Clone, Copy)]
+    /// enum ErrorType {
+    ///     Syntax,
+    /// }
+    /// impl Error for ErrorType {
+    ///     fn handle(&self) {  }
+    ///     fn as_str(&self) -> &str {
+    ///         "Syntax Error"
+    ///     }
+    /// }
+    ///
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum FlagType {
+    ///     Verbose,
+    ///     Out,
+    /// }
+    /// impl ArgumentType for FlagType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "-v" | "--verbose" => Ok(Self::Verbose),
+    ///             "-o" | "--out" => Ok(Self::Out),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    /// }
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum ActionType {
+    ///     Add,
+    /// }
+    /// impl ArgumentType for ActionType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///         -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "a" | "add" => Ok(Self::Add),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let contents = "# defaults\n--verbose\n--out = build\n\nadd\n";
+    /// let config: Arguments<FlagType, ActionType> =
+    ///     match Arguments::from_config_str(contents, ErrorType::Syntax) {
+    ///         Ok(a) => a,
+    ///         Err(_) => Arguments::new(),
+    ///     };
+    ///
+    /// assert_eq!(config.flags[1].value, Some("build".to_string()));
+    /// assert_eq!(config.actions[0], ActionType::Add);
+    /// ```
+    pub fn from_config_str<E: Error + Clone>(
+        contents: &str,
+        error: E,
+    ) -> Result<Arguments<F, A>, ParseFailure<E>> {
+        let mut tokens = Vec::new();
+        let mut lines = Vec::new();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // Tighten `key = value` into `key=value`:
+            tokens.push(match line.split_once('=') {
+                Some((key, value)) => format!("{}={}", key.trim_end(), value.trim_start()),
+                None => line.to_string(),
+            });
+            lines.push((number + 1, line));
+        }
+
+        let env_args = tokens.iter().map(String::as_str).collect();
+        Self::parse_with(env_args, error, &ParseOptions::new()).map_err(|failure| {
+            match lines.get(failure.index) {
+                Some(&(number, line)) => ParseFailure {
+                    line: Some(number),
+                    ..ParseFailure::new(failure.error, failure.index, line)
+                },
+                None => failure,
+            }
+        })
+    }
+    /// Parses the flags before the first action as global flags, then hands
+    /// the action and everything after it to `dispatch`. This lets each
+    /// action parse its own arguments, typically with its own flag type.
+    /// The action is found as by `Arguments::split_at_first_action`, so it
+    /// may follow a `--`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use adante::{ArgumentType, Arguments, Error};
+    ///
+    /// #[derive(Debug, Clone, Copy)]
+    /// enum ErrorType {
+    ///     Syntax,
+    /// }
+    /// impl Error for ErrorType {
+    ///     fn handle(&self) {  }
+    ///     fn as_str(&self) -> &str {
+    ///         "Syntax Error"
+    ///     }
+    /// }
+    ///
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum FlagType {
+    ///     Verbose,
+    /// }
+    /// impl ArgumentType for FlagType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "-v" | "--verbose" => Ok(Self::Verbose),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    /// }
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum RemoteFlagType {
+    ///     Name,
+    /// }
+    /// impl ArgumentType for RemoteFlagType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "--name" => Ok(Self::Name),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    /// }
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum ActionType {
+    ///     Remote,
+    ///     Add,
+    /// }
+    /// impl ArgumentType for ActionType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///         -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "remote" => Ok(Self::Remote),
+    ///             "add" => Ok(Self::Add),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let env_args = vec!["-v", "remote", "add", "--name=origin"];
+    /// let result = Arguments::<FlagType, ActionType>::parse_subcommands(
+    ///     env_args,
+    ///     ErrorType::Syntax,
+    ///     |_action, rest| Arguments::<RemoteFlagType, ActionType>::parse(rest.to_vec(), ErrorType::Syntax),
+    /// );
+    /// let parsed = match result {
+    ///     Ok(p) => p,
+    ///     Err(_) => panic!("failed to parse"),
+    /// };
+    ///
+    /// assert_eq!(parsed.global[0].key, FlagType::Verbose);
+    /// assert_eq!(parsed.action, Some(ActionType::Remote));
+    /// assert_eq!(parsed.sub.unwrap().flags[0].key, RemoteFlagType::Name);
+    /// ```
+    pub fn parse_subcommands<E, S, D>(
+        env_args: Vec<&str>,
+        error: E,
+        dispatch: D,
+    ) -> Result<SubcommandArgs<F, A, S>, E>
+    where
+        E: Error + Clone,
+        D: FnOnce(&A, &[&str]) -> Result<S, E>,
+    {
+        // Split at the first argument that isn't a flag, or the one after `--`:
+        let (flags, split) = match Self::split_at_first_action(&env_args) {
+            Some((i, flags, rest)) => (flags, Some((env_args[i], rest))),
+            None => (env_args.strip_suffix(&["--"]).unwrap_or(&env_args), None),
+        };
+        let global: Arguments<F, A> = Self::parse(flags.to_vec(), error.clone())?;
+        let mut result = SubcommandArgs {
+            global: global.flags,
+            action: None,
+            sub: None,
+        };
+        if let Some((action, rest)) = split {
+            let action = A::from_str(action, error.unknown_action())?;
+            result.sub = Some(dispatch(&action, rest)?);
+            result.action = Some(action);
+        }
+
+        Ok(result)
+    }
+    /// Finds the first argument that isn't a flag and returns its index
+    /// along with the arguments before and after it, so that each side can
+    /// be parsed with its own `Arguments` type. Returns `None` when there is
+    /// no action.
+    ///
+    /// A `--` argument ends the flags: the argument following it is the
+    /// action even if it starts with `-`, and the `--` itself is left out of
+    /// the returned slices. Values are always attached with `=`, so a split
+    /// never lands inside a flag's value.
+    pub fn split_at_first_action<'a>(
+        args: &'a [&'a str],
+    ) -> Option<(usize, &'a [&'a str], &'a [&'a str])> {
+        for (i, arg) in args.iter().enumerate() {
+            if *arg == "--" {
+                return args.get(i + 1).map(|_| (i + 1, &args[..i], &args[(i + 2)..]));
+            }
+            if !arg.starts_with('-') {
+                return Some((i, &args[..i], &args[(i + 1)..]));
+            }
+        }
+        None
+    }
+    /// Returns the index of the first argument that is exactly one of
+    /// `keys`, stopping at `--`, without looking anything up with
+    /// `ArgumentType::from_str` or allocating. Lets a tool answer `--help`
+    /// or `--version` before paying for a full parse and validation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use adante::{Arguments, common::CommonFlags};
+    ///
+    /// type Args = Arguments<CommonFlags, CommonFlags>;
+    ///
+    /// let keys = ["-h", "--help"];
+    /// assert_eq!(Args::prescan(&["x", "--help"], &keys), Some(1));
+    /// assert_eq!(Args::prescan(&["--", "-h"], &keys), None);
+    /// ```
+    pub fn prescan<S: AsRef<str>>(env_args: &[S], keys: &[&str]) -> Option<usize> {
+        env_args
+            .iter()
+            .map(AsRef::as_ref)
+            .take_while(|&arg| arg != "--")
+            .position(|arg| keys.contains(&arg))
+    }
+    /// Returns help text listing the keys of every flag and action variant,
+    /// one variant per line, such as `  -h, --help`.
+    ///
+    /// The variants come from `ArgumentType::variants` and their keys from
+    /// `ArgumentType::known_keys`; a variant without any known key is left
+    /// out. Each line ends with the variant's `ArgumentType::describe` text,
+    /// if it has any.
+    ///
+    /// With the `color` feature, the keys are bold when stdout is a terminal
+    /// and `NO_COLOR` isn't set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use adante::{ArgumentType, Arguments};
+    ///
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum FlagType {
+    ///     Help,
+    ///     Verbose,
+    /// }
+    /// impl ArgumentType for FlagType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "-h" | "--help" => Ok(Self::Help),
+    ///             "-v" | "--verbose" => Ok(Self::Verbose),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    ///     fn known_keys() -> &'static [&'static str] {
+    ///         &["-h", "--help", "-v", "--verbose"]
+    ///     }
+    ///     fn variants() -> &'static [Self] {
+    ///         &[Self::Help, Self::Verbose]
+    ///     }
+    /// }
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum ActionType {
+    ///     Add,
+    /// }
+    /// impl ArgumentType for ActionType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///         -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "a" | "add" => Ok(Self::Add),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    ///     fn known_keys() -> &'static [&'static str] {
+    ///         &["add", "a"]
+    ///     }
+    ///     fn variants() -> &'static [Self] {
+    ///         &[Self::Add]
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(
+    ///     Arguments::<FlagType, ActionType>::usage(),
+    ///     "Flags:\n  -h, --help\n  -v, --verbose\n\nActions:\n  add, a\n"
+    /// );
+    /// ```
+    pub fn usage() -> String
+    where
+        F: PartialEq + 'static,
+        A: PartialEq + 'static,
+    {
+        Self::usage_painted(color::stdout_colored(), &Messages::ENGLISH)
+    }
+    /// Returns the help text from `usage`, with the headings taken from
+    /// the catalog set by `ParseOptions::messages`.
+    pub fn usage_for(options: &ParseOptions) -> String
+    where
+        F: PartialEq + 'static,
+        A: PartialEq + 'static,
+    {
+        Self::usage_painted(color::stdout_colored(), options.message_catalog())
+    }
+    /// Returns the help text from `usage`, colored as the given choice
+    /// says.
+    ///
+    /// Requires the `color` feature.
+    #[cfg(feature = "color")]
+    pub fn usage_with(choice: ColorChoice) -> String
+    where
+        F: PartialEq + 'static,
+        A: PartialEq + 'static,
+    {
+        use std::io::IsTerminal;
+        Self::usage_painted(choice.resolve(std::io::stdout().is_terminal()), &Messages::ENGLISH)
+    }
+    // Builds the help text, with bold keys if color is enabled
+    fn usage_painted(color: bool, messages: &Messages) -> String
+    where
+        F: PartialEq + 'static,
+        A: PartialEq + 'static,
+    {
+        let mut usage = format!("{}\n", messages.flags_heading);
+        for variant in F::variants() {
+            push_usage_line(&mut usage, &keys_of(variant), variant.describe(), color);
+        }
+        usage.push('\n');
+        usage.push_str(messages.actions_heading);
+        usage.push('\n');
+        for variant in A::variants() {
+            push_usage_line(&mut usage, &keys_of(variant), variant.describe(), color);
+        }
+        usage
+    }
+    // Parses into owned values, passing warnings to the options' hook
+    fn parse_owned<E: Error + Clone>(
+        env_args: Vec<&str>,
+        error: E,
+        options: &ParseOptions,
+        same_key: Option<fn(&F, &F) -> bool>,
+    ) -> Result<Arguments<F, A>, ParseFailure<E>> {
+        let mut warnings = Vec::new();
+        let result = Self::parse_collecting(env_args, error, options, same_key, &mut warnings);
+        for warning in warnings {
+            options.warn(warning);
+        }
+        result
+    }
+    // Parses into owned values, collecting warnings and handing any failure
+    // to `Error::handle_with` if enabled
+    fn parse_collecting<E: Error + Clone>(
+        env_args: Vec<&str>,
+        error: E,
+        options: &ParseOptions,
+        same_key: Option<fn(&F, &F) -> bool>,
+        warnings: &mut Vec<Warning>,
+    ) -> Result<Arguments<F, A>, ParseFailure<E>> {
+        let result = Self::parse_expanded(&env_args, error, options, same_key, warnings)
+            .map_err(|failure| {
+                let failure = failure.locate(&env_args).with_candidates::<F>(options);
+                failure.with_messages(options.message_catalog())
+            });
+        if let Err(failure) = &result {
+            if options.handles_errors() {
+                failure.error.handle_with(&failure.token);
+            }
+        }
+        result
+    }
+    // Parses into owned values, expanding response files, aliases and
+    // rewritten flags first if enabled
+    fn parse_expanded<E: Error + Clone>(
+        env_args: &[&str],
+        error: E,
+        options: &ParseOptions,
+        same_key: Option<fn(&F, &F) -> bool>,
+        warnings: &mut Vec<Warning>,
+    ) -> Result<Arguments<F, A>, ParseFailure<E>> {
+        if !Expander::is_needed(options) {
+            return Self::parse_tokens(env_args, error, options, same_key, warnings);
+        }
+        // The expanded arguments, along with the index each one came from
+        let mut expander = Expander::default();
+        let mut tokens = Vec::with_capacity(env_args.len());
+        let mut origins = Vec::with_capacity(env_args.len());
+        for (index, arg) in env_args.iter().enumerate() {
+            expander
+                .expand::<F>(arg, options, &mut tokens)
+                .map_err(|bad| ParseFailure::new(error.clone(), index, &bad))?;
+            origins.resize(tokens.len(), index);
+        }
+
+        let tokens: Vec<&str> = tokens.iter().map(String::as_str).collect();
+        let first = warnings.len();
+        let result = Self::parse_tokens(&tokens, error, options, same_key, warnings);
+        // Point back at the arguments the tokens were expanded from:
+        for warning in &mut warnings[first..] {
+            warning.index = origins.get(warning.index).copied().unwrap_or(warning.index);
+        }
+        result.map_err(|failure| {
+            let index = origins.get(failure.index).copied().unwrap_or(env_args.len());
+            ParseFailure { index, ..failure }
+        })
+    }
+}
+
+impl<F: ArgumentType + fmt::Debug, A: fmt::Debug, V: fmt::Debug> Arguments<F, A, V> {
+    /// Formats the arguments like their `Debug` impl, but with the values
+    /// of sensitive flags masked, as by `Flag::redacted_debug`.
+    pub fn redacted_debug(&self) -> String {
+        format!("{:?}", RedactedArguments(self))
+    }
+}
+
+// Formats arguments like their derived `Debug` impl, with redacted flags
+struct RedactedArguments<'a, F, A, V>(&'a Arguments<F, A, V>);
+
+impl<F: ArgumentType + fmt::Debug, A: fmt::Debug, V: fmt::Debug> fmt::Debug
+    for RedactedArguments<'_, F, A, V>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let args = self.0;
+        f.debug_struct("Arguments")
+            .field("flags", &args.flags.iter().map(Redacted).collect::<Vec<_>>())
+            .field("actions", &args.actions)
+            .field("positionals", &args.positionals)
+            .field("passthrough", &args.passthrough)
+            .finish()
+    }
+}
+
+impl<F, A, V> Arguments<F, A, V> {
+    /// Returns the parsed flags, in the order they were passed.
+    pub fn flags(&self) -> &[Flag<F, V>] {
+        &self.flags
+    }
+    /// Returns the parsed actions, in the order they were passed.
+    pub fn actions(&self) -> &[A] {
+        &self.actions
+    }
+    /// Retains only the flags for which the predicate returns `true`,
+    /// preserving their order. Useful for stripping internal-only flags
+    /// before forwarding the rest.
+    pub fn retain_flags<P: FnMut(&Flag<F, V>) -> bool>(&mut self, mut pred: P) {
+        let mut removed = Vec::new();
+        let mut index = 0;
+        self.flags.retain(|flag| {
+            let keep = pred(flag);
+            if !keep {
+                removed.push(index);
+            }
+            index += 1;
+            keep
+        });
+        // Actions now come after fewer flags:
+        for offset in &mut self.action_offsets {
+            *offset -= removed.iter().take_while(|&&i| i < *offset).count();
+        }
+    }
+    /// Appends a flag, as if it had been passed after the others. Like
+    /// every flag that wasn't typed, it's recorded as `FlagStyle::Long`.
+    pub fn add_flag(&mut self, key: F, value: Option<V>) {
+        self.flags.push(Flag {
+            key,
+            value,
+            values: Vec::new(),
+            style: FlagStyle::Long,
+        });
+    }
+    /// Appends an action, as if it had been passed after the others.
+    pub fn add_action(&mut self, action: A) {
+        self.action_offsets.push(self.flags.len());
+        self.actions.push(action);
+    }
+    /// Keeps only the first occurrence of each flag key, dropping later
+    /// repeats, for when the value passed first should win.
+    pub fn dedup_flags_keep_first(&mut self)
+    where
+        F: PartialEq,
+    {
+        let flags = &self.flags;
+        let keep: Vec<bool> =
+            (0..flags.len()).map(|i| !flags[..i].iter().any(|f| f.key == flags[i].key)).collect();
+        let mut keep = keep.into_iter();
+        self.retain_flags(|_| keep.next().unwrap_or(true));
+    }
+    /// Keeps only the last occurrence of each flag key, dropping earlier
+    /// ones, for when a later value overrides an earlier one.
+    pub fn dedup_flags_keep_last(&mut self)
+    where
+        F: PartialEq,
+    {
+        let flags = &self.flags;
+        let keep: Vec<bool> =
+            (0..flags.len()).map(|i| !flags[i + 1..].iter().any(|f| f.key == flags[i].key)).collect();
+        let mut keep = keep.into_iter();
+        self.retain_flags(|_| keep.next().unwrap_or(true));
+    }
+    /// Returns the flags passed before the first action, or every flag if
+    /// there are no actions.
+    pub fn global_flags(&self) -> &[Flag<F, V>] {
+        &self.flags[..self.flags_before(0)]
+    }
+    /// Returns the flags passed after the `n`th action, counting from zero,
+    /// and before the next one, such as `-f` in `-v add -f`.
+    pub fn flags_after_action(&self, n: usize) -> &[Flag<F, V>] {
+        if n >= self.actions.len() {
+            return &[];
+        }
+        // Offsets read from untrusted data may be out of order:
+        let start = self.flags_before(n);
+        &self.flags[start..self.flags_before(n + 1).max(start)]
+    }
+    // Returns how many flags were passed before the `n`th action, or all of
+    // them if there's no such action or it wasn't added through the parser
+    // or `add_action`
+    fn flags_before(&self, n: usize) -> usize {
+        match self.action_offsets.get(n) {
+            Some(&offset) if n < self.actions.len() => offset.min(self.flags.len()),
+            _ => self.flags.len(),
+        }
+    }
+    /// Removes every occurrence of a flag, returning how many there were.
+    pub fn remove_flag(&mut self, key: &F) -> usize
+    where
+        F: PartialEq,
+    {
+        let before = self.flags.len();
+        self.retain_flags(|flag| flag.key != *key);
+        before - self.flags.len()
+    }
+    /// Removes and returns the first occurrence of a flag, for flags that
+    /// should be consumed rather than forwarded.
+    pub fn take_flag(&mut self, key: F) -> Option<Flag<F, V>>
+    where
+        F: PartialEq,
+    {
+        let index = self.flags.iter().position(|flag| flag.key == key)?;
+        // Actions after it now come after one fewer flag:
+        for offset in &mut self.action_offsets {
+            if *offset > index {
+                *offset -= 1;
+            }
+        }
+        Some(self.flags.remove(index))
+    }
+    /// Consumes the Arguments object, returning its flags and actions so
+    /// they can be moved out without cloning.
+    pub fn into_parts(self) -> (Vec<Flag<F, V>>, Vec<A>) {
+        (self.flags, self.actions)
+    }
+    /// Returns whether the given flag was passed.
+    pub fn contains_flag(&self, key: F) -> bool
+    where
+        F: PartialEq,
+    {
+        self.flags.iter().any(|flag| flag.key == key)
+    }
+    /// Returns whether the given action was passed.
+    pub fn contains_action(&self, action: A) -> bool
+    where
+        A: PartialEq,
+    {
+        self.actions.contains(&action)
+    }
+    /// Returns the number of actions passed.
+    pub fn action_count(&self) -> usize {
+        self.actions.len()
+    }
+    /// Returns `error` unless the number of actions passed is between
+    /// `min` and `max`, inclusive.
+    pub fn require_action_count<E>(&self, min: usize, max: usize, error: E) -> Result<(), E> {
+        if (min..=max).contains(&self.actions.len()) {
+            Ok(())
+        } else {
+            Err(error)
+        }
+    }
+    /// Returns the one action that was passed, or `error` if there were
+    /// none or more than one, for tools that need exactly one command.
+    pub fn require_action<E>(&self, error: E) -> Result<&A, E> {
+        match self.actions.as_slice() {
+            [action] => Ok(action),
+            _ => Err(error),
+        }
+    }
+    /// Returns `error` if any flag key was passed more than once, for
+    /// strict tools that forbid repeating a flag.
+    pub fn reject_duplicates<E>(&self, error: E) -> Result<(), E>
+    where
+        F: PartialEq,
+    {
+        let repeated = self
+            .flags
+            .iter()
+            .enumerate()
+            .any(|(i, flag)| self.flags[..i].iter().any(|earlier| earlier.key == flag.key));
+        if repeated {
+            Err(error)
+        } else {
+            Ok(())
+        }
+    }
+    /// Returns a compact, single line description of the parsed arguments
+    /// for logging, such as `flags=[Help, Verbose=x] actions=[Add]`.
+    pub fn summary(&self) -> String
+    where
+        F: fmt::Debug,
+        A: fmt::Debug,
+        V: AsRef<str>,
+    {
+        let flags: Vec<String> = self
+            .flags
+            .iter()
+            .map(|flag| match &flag.value {
+                Some(value) => format!("{:?}={}", flag.key, value.as_ref()),
+                None => format!("{:?}", flag.key),
+            })
+            .collect();
+        let actions: Vec<String> = self.actions.iter().map(|a| format!("{:?}", a)).collect();
+        format!("flags=[{}] actions=[{}]", flags.join(", "), actions.join(", "))
+    }
+    /// Returns the parsed arguments as a JSON string, with keys and actions
+    /// written using their `Debug` representations, for example
+    /// `{"flags":[{"key":"Verbose","value":null}],"actions":["Add"]}`.
+    /// A flag's `values`, the `positionals` and the `passthrough` pairs, as
+    /// two-element arrays, are only written when there are some.
+    ///
+    /// This doesn't need the `serde` feature.
+    pub fn to_json(&self) -> String
+    where
+        F: fmt::Debug,
+        A: fmt::Debug,
+        V: AsRef<str>,
+    {
+        let mut json = String::from("{\"flags\":[");
+        for (i, flag) in self.flags.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            json.push_str("{\"key\":");
+            push_json_string(&mut json, &format!("{:?}", flag.key));
+            json.push_str(",\"value\":");
+            match &flag.value {
+                Some(value) => push_json_string(&mut json, value.as_ref()),
+                None => json.push_str("null"),
+            }
+            if !flag.values.is_empty() {
+                json.push_str(",\"values\":");
+                push_json_strings(&mut json, flag.values.iter().map(AsRef::as_ref));
+            }
+            json.push('}');
+        }
+        json.push_str("],\"actions\":[");
+        for (i, action) in self.actions.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            push_json_string(&mut json, &format!("{:?}", action));
+        }
+        json.push(']');
+        if !self.positionals.is_empty() {
+            json.push_str(",\"positionals\":");
+            push_json_strings(&mut json, self.positionals.iter().map(AsRef::as_ref));
+        }
+        if !self.passthrough.is_empty() {
+            json.push_str(",\"passthrough\":[");
+            for (i, (key, value)) in self.passthrough.iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+                push_json_strings(&mut json, [key.as_str(), value.as_str()].iter().copied());
+            }
+            json.push(']');
+        }
+        json.push('}');
+        json
+    }
+    /// Writes the arguments back out as a list that parses into the same
+    /// `Arguments`: the rendered flags, each followed by its greedy values,
+    /// then the actions and positionals, then `--` and the passthrough pairs
+    /// if there are any. See `Flag::render`.
+    pub fn to_args(&self) -> Vec<String>
+    where
+        F: ArgumentType,
+        A: ArgumentType,
+        V: AsRef<str>,
+    {
+        let mut args = Vec::new();
+        for flag in &self.flags {
+            args.push(flag.render());
+            args.extend(flag.values.iter().map(|value| value.as_ref().to_string()));
+        }
+        args.extend(self.actions.iter().map(|action| action.canonical_key().to_string()));
+        args.extend(self.positionals.iter().map(|value| value.as_ref().to_string()));
+        if !self.passthrough.is_empty() {
+            args.push("--".to_string());
+            args.extend(self.passthrough.iter().map(|(key, value)| format!("{}={}", key, value)));
+        }
+        args
+    }
+    /// Returns the values of every occurrence of a flag, in the order they
+    /// were passed. Occurrences without a value are skipped.
+    pub fn all_values_of(&self, key: F) -> Vec<&str>
+    where
+        F: PartialEq,
+        V: AsRef<str>,
+    {
+        self.flags
+            .iter()
+            .filter(|flag| flag.key == key)
+            .filter_map(|flag| flag.value.as_ref().map(AsRef::as_ref))
+            .collect()
+    }
+    /// Collects the flags into a map from each key to its value, as a
+    /// lookup table for configuration systems. A key passed more than once
+    /// maps to the value of its last occurrence, even if that is `None`.
+    ///
+    /// Requires the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn to_map(&self) -> std::collections::HashMap<F, Option<String>>
+    where
+        F: Eq + core::hash::Hash + Clone,
+        V: AsRef<str>,
+    {
+        self.flags
+            .iter()
+            .map(|flag| (flag.key.clone(), flag.value.as_ref().map(|v| v.as_ref().to_string())))
+            .collect()
+    }
+    /// Groups the flags by key, mapping each to the values of its
+    /// occurrences in the order they were passed, with `None` for those
+    /// without one. The values are copied, leaving the arguments as they
+    /// were.
+    ///
+    /// Requires the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn flag_map(&self) -> std::collections::HashMap<F, Vec<Option<String>>>
+    where
+        F: Eq + core::hash::Hash + Clone,
+        V: AsRef<str>,
+    {
+        let mut map = std::collections::HashMap::new();
+        for flag in &self.flags {
+            let value = flag.value.as_ref().map(|v| v.as_ref().to_string());
+            map.entry(flag.key.clone()).or_insert_with(Vec::new).push(value);
+        }
+        map
+    }
+    /// Like `flag_map`, but keeps only the last value given for each key,
+    /// as found by `last_value_of`. Unlike `to_map`, a later occurrence
+    /// without a value doesn't hide an earlier value.
+    ///
+    /// Requires the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn flag_map_last(&self) -> std::collections::HashMap<F, Option<String>>
+    where
+        F: Eq + core::hash::Hash + Clone,
+        V: AsRef<str>,
+    {
+        let mut map = std::collections::HashMap::new();
+        for flag in &self.flags {
+            let value = flag.value.as_ref().map(|v| v.as_ref().to_string());
+            let last = map.entry(flag.key.clone()).or_insert(None);
+            if value.is_some() {
+                *last = value;
+            }
+        }
+        map
+    }
+    /// Collects the `name=value` pairs given to a map flag, such as
+    /// `-Dname=value`, into a map (see `ArgumentType::is_map`). A name given
+    /// more than once maps to its last value, and a value without `=` maps
+    /// to an empty string unless `ParseOptions::require_map_pairs` rejected
+    /// it while parsing.
+    ///
+    /// Requires the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn map_values(&self, key: F) -> std::collections::HashMap<String, String>
+    where
+        F: PartialEq,
+        V: AsRef<str>,
+    {
+        self.flags
+            .iter()
+            .filter(|flag| flag.key == key)
+            .filter_map(|flag| flag.value.as_ref())
+            .map(|value| match value.as_ref().split_once('=') {
+                Some((name, value)) => (name.to_string(), value.to_string()),
+                None => (value.as_ref().to_string(), String::new()),
+            })
+            .collect()
+    }
+    /// Returns whether a flag was passed and, if so, its value, answering
+    /// both questions with one `match`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use adante::{ArgumentType, Arguments, FlagMatch, ParseError};
+    ///
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum FlagType {
+    ///     Verbose,
+    ///     Out,
+    /// }
+    /// impl ArgumentType for FlagType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "-v" | "--verbose" => Ok(Self::Verbose),
+    ///             "-o" | "--out" => Ok(Self::Out),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    /// }
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum ActionType {
+    ///     Add,
+    /// }
+    /// impl ArgumentType for ActionType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///         -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "a" | "add" => Ok(Self::Add),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let args: Arguments<FlagType, ActionType> =
+    ///     Arguments::parse(vec!["--out=build"], ParseError::UnknownFlag).unwrap();
+    ///
+    /// match args.get(FlagType::Out) {
+    ///     FlagMatch::Value(path) => assert_eq!(path, "build"),
+    ///     FlagMatch::Present | FlagMatch::Absent => panic!("expected a value"),
+    /// }
+    /// assert_eq!(args.get(FlagType::Verbose), FlagMatch::Absent);
+    /// ```
+    pub fn get(&self, key: F) -> FlagMatch<'_>
+    where
+        F: PartialEq,
+        V: AsRef<str>,
+    {
+        let mut found = FlagMatch::Absent;
+        for flag in self.flags.iter().filter(|flag| flag.key == key) {
+            found = match &flag.value {
+                Some(value) => FlagMatch::Value(value.as_ref()),
+                None if found == FlagMatch::Absent => FlagMatch::Present,
+                None => found,
+            };
+        }
+        found
+    }
+    /// Returns the value of the last occurrence of a flag that has one, for
+    /// flags where a later occurrence overrides an earlier one.
+    pub fn last_value_of(&self, key: F) -> Option<&str>
+    where
+        F: PartialEq,
+        V: AsRef<str>,
+    {
+        self.flags
+            .iter()
+            .rev()
+            .filter(|flag| flag.key == key)
+            .find_map(|flag| flag.value.as_ref().map(AsRef::as_ref))
+    }
+    /// Returns the value of the `n`th occurrence of a flag, counting from
+    /// zero, for flags whose repeats mean different things. Occurrences
+    /// without a value are counted too, and give `None`.
+    pub fn nth_value_of(&self, key: F, n: usize) -> Option<&str>
+    where
+        F: PartialEq,
+        V: AsRef<str>,
+    {
+        self.flags.iter().filter(|flag| flag.key == key).nth(n)?.value.as_ref().map(AsRef::as_ref)
+    }
+    /// Returns the value of a flag, as found by `last_value_of`, or
+    /// `default` if it wasn't passed with a value.
+    pub fn value_or<'a>(&'a self, key: F, default: &'a str) -> &'a str
+    where
+        F: PartialEq,
+        V: AsRef<str>,
+    {
+        self.last_value_of(key).unwrap_or(default)
+    }
+    /// Parses the value of a flag, as found by `last_value_of`, into any
+    /// type that implements `FromStr`. Returns `Ok(None)` if the flag wasn't
+    /// passed with a value.
+    pub fn typed_value<T: FromStr>(&self, key: F) -> Result<Option<T>, T::Err>
+    where
+        F: PartialEq,
+        V: AsRef<str>,
+    {
+        self.last_value_of(key).map(str::parse).transpose()
+    }
+    /// Like `typed_value`, but returns `default` if the flag wasn't passed
+    /// with a value, including when it was passed without one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use adante::{ArgumentType, Arguments, ParseError};
+    ///
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum FlagType {
+    ///     Threads,
+    /// }
+    /// impl ArgumentType for FlagType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "-j" | "--threads" => Ok(Self::Threads),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    /// }
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum ActionType {
+    ///     Build,
+    /// }
+    /// impl ArgumentType for ActionType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///         -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "build" => Ok(Self::Build),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let args: Arguments<FlagType, ActionType> =
+    ///     Arguments::parse(vec!["build", "-j=8"], ParseError::UnknownFlag).unwrap();
+    /// assert_eq!(args.typed_value_or(FlagType::Threads, 4), Ok(8));
+    ///
+    /// let args: Arguments<FlagType, ActionType> =
+    ///     Arguments::parse(vec!["build"], ParseError::UnknownFlag).unwrap();
+    /// assert_eq!(args.typed_value_or(FlagType::Threads, 4), Ok(4));
+    /// ```
+    pub fn typed_value_or<T: FromStr>(&self, key: F, default: T) -> Result<T, T::Err>
+    where
+        F: PartialEq,
+        V: AsRef<str>,
+    {
+        Ok(self.typed_value(key)?.unwrap_or(default))
+    }
+    /// Like `typed_value_or`, but fails with `error` if the flag was passed
+    /// without a value. A value that doesn't parse is converted into the
+    /// error type, so both failures can be reported with `?`.
+    pub fn typed_value_required<T, E>(&self, key: F, default: T, error: E) -> Result<T, E>
+    where
+        F: PartialEq,
+        V: AsRef<str>,
+        T: FromStr,
+        E: From<T::Err>,
+    {
+        match self.get(key) {
+            FlagMatch::Value(value) => Ok(value.parse()?),
+            FlagMatch::Present => Err(error),
+            FlagMatch::Absent => Ok(default),
+        }
+    }
+}
+
+impl<F: ArgumentType, A: ArgumentType, V> Arguments<F, A, V> {
+    fn parse_tokens<'a, E: Error + Clone>(
+        env_args: &[&'a str],
+        error: E,
+        options: &ParseOptions,
+        same_key: Option<fn(&F, &F) -> bool>,
+        warnings: &mut Vec<Warning>,
+    ) -> Result<Arguments<F, A, V>, ParseFailure<E>>
+    where
+        V: From<&'a str>,
+    {
+        let mut args = Arguments::default();
+        args.flags.reserve(env_args.len());
+        args.actions.reserve(env_args.len());
+        let mut recognizer = Recognizer::new(error);
+        let mut items = Vec::new();
+        let mut index = 0;
+        while let Some(&arg) = env_args.get(index) {
+            let fail = |e: E| ParseFailure::new(e, index, arg);
+            let window = (&env_args[index..], true);
+            let used = recognizer
+                .step(window, (index, &(0..0)), options, &mut items, warnings)
+                .map_err(fail)?;
+            for item in items.drain(..) {
+                match item {
+                    Item::Flag(flag) => {
+                        if !args.push_flag(convert_flag(flag), options, same_key) {
+                            return Err(fail(recognizer.error.clone()));
+                        }
+                    }
+                    Item::Action(action) => args.add_action(action),
+                    Item::Positional(value) => args.positionals.push(V::from(value)),
+                    Item::Pair(key, value) => {
+                        args.passthrough.push((key.to_string(), value.to_string()))
+                    }
+                }
+            }
+            // Nothing waits for more at the end of the list:
+            index += used.unwrap_or(1);
+        }
+        recognizer.finish((env_args.len(), 0..0), options)?;
+
+        Ok(args)
+    }
+    // Pushes a parsed flag, applying the duplicate policy if keys can be
+    // compared. Returns false if the policy rejects the flag.
+    fn push_flag(
+        &mut self,
+        flag: Flag<F, V>,
+        options: &ParseOptions,
+        same_key: Option<fn(&F, &F) -> bool>,
+    ) -> bool {
+        let policy = options.duplicates();
+        if let Some(same_key) = same_key {
+            let earlier = self.flags.iter().position(|f| same_key(&f.key, &flag.key));
+            match (policy, earlier) {
+                (DuplicatePolicy::Error, Some(_)) => return false,
+                (DuplicatePolicy::FirstWins, Some(_)) => return true,
+                (DuplicatePolicy::LastWins, Some(i)) => {
+                    self.flags.remove(i);
+                    for offset in self.action_offsets.iter_mut().filter(|offset| **offset > i) {
+                        *offset -= 1;
+                    }
+                }
+                _ => (),
+            }
+        }
+        self.flags.push(flag);
+        true
+    }
+}
+
+impl<'a, F: ArgumentType, A: ArgumentType> Arguments<F, A, &'a str> {
+    /// Parses like `Arguments::parse`, but borrows flag values from the
+    /// arguments instead of allocating a `String` for each one, so the
+    /// result can't outlive `env_args`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use adante::{ArgumentType, Arguments, ParseError};
+    ///
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum FlagType {
+    ///     Out,
+    /// }
+    /// impl ArgumentType for FlagType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "-o" | "--out" => Ok(Self::Out),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    /// }
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum ActionType {
+    ///     Add,
+    /// }
+    /// impl ArgumentType for ActionType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///         -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "a" | "add" => Ok(Self::Add),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let env_args = vec!["--out=build", "add"];
+    /// let args: Arguments<FlagType, ActionType, &str> =
+    ///     Arguments::parse_borrowed(&env_args, ParseError::UnknownFlag).unwrap();
+    ///
+    /// assert_eq!(args.flags[0].value, Some("build"));
+    /// ```
+    pub fn parse_borrowed<E: Error + Clone>(
+        env_args: &[&'a str],
+        error: E,
+    ) -> Result<Arguments<F, A, &'a str>, E> {
+        Self::parse_tokens(env_args, error, &ParseOptions::new(), None, &mut Vec::new())
+            .map_err(|failure| failure.error)
+    }
+}
+
+impl<'a, F: ArgumentType, A: ArgumentType> Arguments<F, A, Cow<'a, str>> {
+    /// Parses like `Arguments::parse_borrowed`, but stores each value as a
+    /// `Cow`: values borrowed from `env_args` don't allocate, while owned
+    /// values can still be added afterwards, such as defaults built at
+    /// runtime.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use adante::{ArgumentType, Arguments, Flag, FlagStyle, ParseError};
+    /// use std::borrow::Cow;
+    ///
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum FlagType {
+    ///     Out,
+    /// }
+    /// impl ArgumentType for FlagType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "-o" | "--out" => Ok(Self::Out),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    /// }
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum ActionType {
+    ///     Add,
+    /// }
+    /// impl ArgumentType for ActionType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///         -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "a" | "add" => Ok(Self::Add),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let env_args = vec!["--out=build", "add"];
+    /// let mut args: Arguments<FlagType, ActionType, Cow<str>> =
+    ///     Arguments::parse_cow(&env_args, ParseError::UnknownFlag).unwrap();
+    /// args.flags.push(Flag {
+    ///     key: FlagType::Out,
+    ///     value: Some(Cow::Owned(format!("{}/{}", "target", "debug"))),
+    ///     values: Vec::new(),
+    ///     style: FlagStyle::Long,
+    /// });
+    ///
+    /// assert_eq!(args.all_values_of(FlagType::Out), vec!["build", "target/debug"]);
+    /// ```
+    pub fn parse_cow<E: Error + Clone>(
+        env_args: &[&'a str],
+        error: E,
+    ) -> Result<Arguments<F, A, Cow<'a, str>>, E> {
+        Self::parse_tokens(env_args, error, &ParseOptions::new(), None, &mut Vec::new())
+            .map_err(|failure| failure.error)
+    }
+}
+
+impl<F: ParseKey, A: ParseKey<Err = F::Err>> Arguments<F, A> {
+    /// Parses flags and actions whose types know their own error through
+    /// `ParseKey`, so no error value needs to be passed in. Types that only
+    /// implement `ArgumentType` work too, reporting a `ParseError`.
+    ///
+    /// Arguments are recognized as `Arguments::parse` recognizes them, with
+    /// each key handed to `ParseKey::parse_key_with_value`. An empty
+    /// argument is handed to the action type's `ParseKey::parse_key`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use adante::{Arguments, Error, ParseKey};
+    ///
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum ErrorType {
+    ///     InvalidFlag,
+    ///     InvalidAction,
+    /// }
+    /// impl Error for ErrorType {
+    ///     fn handle(&self) {  }
+    ///     fn as_str(&self) -> &str {
+    ///         match self {
+    ///             Self::InvalidFlag => "Invalid flag",
+    ///             Self::InvalidAction => "Invalid action",
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum FlagType {
+    ///     Out,
+    /// }
+    /// impl ParseKey for FlagType {
+    ///     type Err = ErrorType;
+    ///     fn parse_key(key: &str) -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "-o" | "--out" => Ok(Self::Out),
+    ///             _ => Err(ErrorType::InvalidFlag),
+    ///         }
+    ///     }
+    /// }
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum ActionType {
+    ///     Add,
+    /// }
+    /// impl ParseKey for ActionType {
+    ///     type Err = ErrorType;
+    ///     fn parse_key(key: &str) -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "a" | "add" => Ok(Self::Add),
+    ///             _ => Err(ErrorType::InvalidAction),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let args = Arguments::<FlagType, ActionType>::parse2(vec!["--out=build", "add"]).unwrap();
+    /// assert_eq!(args.flags[0].value, Some("build".to_string()));
+    ///
+    /// let result = Arguments::<FlagType, ActionType>::parse2(vec!["frob"]);
+    /// assert_eq!(result.unwrap_err(), ErrorType::InvalidAction);
+    /// ```
+    pub fn parse2(env_args: Vec<&str>) -> Result<Arguments<F, A>, F::Err> {
+        let options = ParseOptions::new();
+        let mut args = Arguments::default();
+        let mut recognizer = Recognizer::new(Deferred);
+        let (mut items, mut warnings) = (Vec::new(), Vec::new());
+        let mut index = 0;
+        while let Some(&arg) = env_args.get(index) {
+            if arg.is_empty() {
+                args.add_action(A::parse_key(arg)?);
+                index += 1;
+                continue;
+            }
+            // Every key is taken, so only an empty argument could fail here
+            let window = (&env_args[index..], true);
+            let step = recognizer.step::<Keyed<F>, Keyed<A>>(
+                window,
+                (index, &(0..0)),
+                &options,
+                &mut items,
+                &mut warnings,
+            );
+            for item in items.drain(..) {
+                match item {
+                    Item::Flag(flag) => args.flags.push(Flag {
+                        key: flag.key.0?,
+                        value: flag.value.map(String::from),
+                        values: flag.values.into_iter().map(String::from).collect(),
+                        style: flag.style,
+                    }),
+                    Item::Action(action) => args.add_action(action.0?),
+                    // Positionals and passthrough pairs are off:
+                    Item::Positional(_) | Item::Pair(..) => (),
+                }
+            }
+            index += step.ok().flatten().unwrap_or(1);
+        }
+
+        Ok(args)
+    }
+}
+
+impl<F: ArgumentType + PartialEq, A: ArgumentType> Arguments<F, A> {
+    /// Fills in flags that weren't passed from environment variables.
+    ///
+    /// For each `(key, variable)` pair whose key is absent, the variable is
+    /// read and, if set, pushed as a flag with the variable's contents as
+    /// its value. Flags already present are left untouched.
+    ///
+    /// Requires the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn fill_from_env(&mut self, mapping: &[(F, &str)])
+    where
+        F: Clone,
+    {
+        for (key, variable) in mapping.iter() {
+            if self.flags.iter().any(|flag| flag.key == *key) {
+                continue;
+            }
+            if let Ok(value) = std::env::var(variable) {
+                self.flags.push(Flag {
+                    key: key.clone(),
+                    value: Some(value),
+                    values: Vec::new(),
+                    style: FlagStyle::Long,
+                });
+            }
+        }
+    }
+    /// Appends a flag for each `(key, value)` default whose key wasn't
+    /// passed, such as `(FlagType::Threads, Some("4"))` to act as if
+    /// `--threads=4` had been given.
+    ///
+    /// Flags already present are never overwritten, even when they were
+    /// passed with an empty value like `--threads=`.
+    pub fn with_defaults(mut self, defaults: &[(F, Option<&str>)]) -> Arguments<F, A>
+    where
+        F: Clone,
+    {
+        for (key, value) in defaults.iter() {
+            if self.flags.iter().any(|flag| flag.key == *key) {
+                continue;
+            }
+            self.flags.push(Flag {
+                key: key.clone(),
+                value: value.map(str::to_string),
+                values: Vec::new(),
+                style: FlagStyle::Long,
+            });
+        }
+        self
+    }
+    /// Parses like `Arguments::parse_with`, additionally applying the
+    /// duplicate flag policy set with `ParseOptions::duplicate_flags`.
+    ///
+    /// With `DuplicatePolicy::Error`, the failure points at the second
+    /// occurrence of the repeated key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use adante::{ArgumentType, Arguments, DuplicatePolicy, Error, ParseOptions};
+    ///
+    /// #[derive(Debug, Clone, Copy)]
+    /// enum ErrorType {
+    ///     Syntax,
+    /// }
+    /// impl Error for ErrorType {
+    ///     fn handle(&self) {  }
+    ///     fn as_str(&self) -> &str {
+    ///         "Syntax Error"
+    ///     }
+    /// }
+    ///
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum FlagType {
+    ///     Out,
+    /// }
+    /// impl ArgumentType for FlagType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "-o" | "--out" => Ok(Self::Out),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    /// }
+    /// enum ActionType {
+    ///     Add,
+    /// }
+    /// impl ArgumentType for ActionType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///         -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "a" | "add" => Ok(Self::Add),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let options = ParseOptions::new().duplicate_flags(DuplicatePolicy::Error);
+    /// let result: Result<Arguments<FlagType, ActionType>, _> =
+    ///     Arguments::parse_with_dedup(vec!["--out=a", "--out=b"], ErrorType::Syntax, &options);
+    ///
+    /// match result {
+    ///     Ok(_) => panic!("expected a duplicate flag error"),
+    ///     Err(failure) => assert_eq!(failure.index, 1),
+    /// }
+    /// ```
+    pub fn parse_with_dedup<E: Error + Clone>(
+        env_args: Vec<&str>,
+        error: E,
+        options: &ParseOptions,
+    ) -> Result<Arguments<F, A>, ParseFailure<E>> {
+        Self::parse_owned(env_args, error, options, Some(F::eq))
+    }
+}
+
+// A stand-in error, handed to `ArgumentType::from_str` so that the real
+// error is only built once an argument fails, and used by
+// `Arguments::parse_with_err`, which only builds it once it knows which
+// argument failed
+#[derive(Clone)]
+pub(crate) struct Deferred;
+
+impl Error for Deferred {
+    fn handle(&self) {}
+    fn as_str(&self) -> &str {
+        ""
+    }
+}
+
+// Adapts a `ParseKey` type to `ArgumentType` for `Arguments::parse2`, so
+// that it can use the same recognizer as `Arguments::parse`. Every key is
+// taken, carrying the type's own error if `parse_key` rejects it.
+struct Keyed<T: ParseKey>(Result<T, T::Err>);
+
+impl<T: ParseKey> ArgumentType for Keyed<T> {
+    fn from_str<E: Error>(key: &str, error: E) -> Result<Self, E> {
+        let _ = error;
+        Ok(Keyed(T::parse_key(key)))
+    }
+    fn from_str_with_value<E: Error>(key: &str, value: Option<&str>, error: E) -> Result<Self, E> {
+        let _ = error;
+        Ok(Keyed(T::parse_key_with_value(key, value)))
+    }
+}
+
+// How many response files may be nested inside each other
+#[cfg(feature = "std")]
+const RESPONSE_FILE_DEPTH: usize = 4;
+
+// Pushes an argument, reading it as a response file if it starts with `@`.
+// Returns the offending `@path` if a file can't be read.
+#[cfg(feature = "std")]
+fn read_response_arg(arg: &str, depth: usize, tokens: &mut Vec<String>) -> Result<(), String> {
+    match arg.strip_prefix('@') {
+        Some(path) if !path.is_empty() => {
+            if depth == RESPONSE_FILE_DEPTH {
+                return Err(arg.to_string());
+            }
+            let contents = std::fs::read_to_string(path).map_err(|_| arg.to_string())?;
+            for token in contents.split_whitespace() {
+                read_response_arg(token, depth + 1, tokens)?;
+            }
+        }
+        _ => tokens.push(arg.to_string()),
+    }
+    Ok(())
+}
+
+// Expands the arguments one at a time before they're recognized: reads
+// response files, rewrites slash and dashless flags, then replaces aliases,
+// as enabled. Keeps track of `--` and greedy values from one argument to
+// the next.
+#[derive(Debug, Default)]
+struct Expander {
+    // Whether a `--` was seen before rewriting, and before replacing aliases
+    rewrite_done: bool,
+    aliases_done: bool,
+    // Whether the arguments are greedy values of the flag before them
+    in_values: bool,
+}
+
+impl Expander {
+    // Returns whether any expansion is enabled
+    fn is_needed(options: &ParseOptions) -> bool {
+        let response_files = cfg!(feature = "std") && options.expands_response_files();
+        response_files
+            || options.allows_slash_flags()
+            || options.allows_dashless_flags()
+            || options.has_aliases()
+    }
+    // Pushes what an argument expands to. Returns the offending `@path` if
+    // a response file can't be read.
+    fn expand<F: ArgumentType>(
+        &mut self,
+        arg: &str,
+        options: &ParseOptions,
+        tokens: &mut Vec<String>,
+    ) -> Result<(), String> {
+        let mut read = Vec::new();
+        #[cfg(feature = "std")]
+        let expanded = options.expands_response_files();
+        #[cfg(not(feature = "std"))]
+        let expanded = false;
+        if expanded {
+            #[cfg(feature = "std")]
+            read_response_arg(arg, 0, &mut read)?;
+        } else {
+            read.push(arg.to_string());
+        }
+        for mut token in read {
+            if options.allows_slash_flags() || options.allows_dashless_flags() {
+                token = self.rewrite::<F>(token, options);
+            }
+            if options.has_aliases() {
+                self.replace_alias::<F>(&token, options, tokens);
+            } else {
+                tokens.push(token);
+            }
+        }
+        Ok(())
+    }
+    // Rewrites `/out:file` as `--out=file` and `/v` as `-v` if slash flags
+    // are enabled, and `out=file` as `--out=file` if dashless flags are, up
+    // to a `--`, when the rewritten key is a known flag
+    fn rewrite<F: ArgumentType>(
+        &mut self,
+        arg: String,
+        options: &ParseOptions,
+    ) -> String {
+        let known = |key: &str, value| lookup_flag::<F, _>(key, value, Deferred, options).is_ok();
+        self.rewrite_done |= arg == "--";
+        let rewritten = match arg.strip_prefix('/') {
+            _ if self.rewrite_done => None,
+            Some(flag) if !flag.is_empty() && options.allows_slash_flags() => {
+                let (name, value) = match flag.split_once(':') {
+                    Some((name, value)) => (name, Some(value)),
+                    None => (flag, None),
+                };
+                let dashes = if name.chars().count() == 1 { "-" } else { "--" };
+                let key = format!("{}{}", dashes, name);
+                known(&key, value).then(|| match value {
+                    Some(value) => format!("{}{}{}", key, options.separator(), value),
+                    None => key,
+                })
+            }
+            _ if options.allows_dashless_flags() && !arg.starts_with('-') => {
+                arg.split_once(options.separator()).and_then(|(name, value)| {
+                    let key = format!("--{}", name);
+                    known(&key, Some(value)).then(|| format!("--{}", arg))
+                })
+            }
+            _ => None,
+        };
+        rewritten.unwrap_or(arg)
+    }
+    // Pushes an argument, replaced by its expansion if it's an alias and
+    // neither follows a `--` nor is a greedy value
+    fn replace_alias<F: ArgumentType>(
+        &mut self,
+        arg: &str,
+        options: &ParseOptions,
+        tokens: &mut Vec<String>,
+    ) {
+        match token::classify(arg, options) {
+            TokenKind::Word if !self.in_values && !self.aliases_done => {
+                push_alias(arg, 0, options, tokens)
+            }
+            TokenKind::Word => tokens.push(arg.to_string()),
+            kind => {
+                // Greedy values follow a flag that takes them
+                self.in_values = match kind {
+                    TokenKind::ShortFlag { key, value: None }
+                    | TokenKind::LongFlag { key, value: None } => {
+                        let flag = lookup_flag::<F, _>(key, None, Deferred, options);
+                        options.collects_greedy_values() && matches!(flag, Ok(flag) if flag.takes_value())
+                    }
+                    _ => false,
+                };
+                self.aliases_done |= kind == TokenKind::Terminator;
+                tokens.push(arg.to_string());
+            }
+        }
+    }
+}
+
+// Pushes an argument, replaced by its expansion if it's an alias
+fn push_alias(arg: &str, depth: usize, options: &ParseOptions, tokens: &mut Vec<String>) {
+    match options.alias(arg) {
+        Some(expansion) if depth < options.max_alias_depth() => {
+            for token in expansion {
+                if token::classify(token, options) == TokenKind::Word {
+                    push_alias(token, depth + 1, options, tokens);
+                } else {
+                    tokens.push(token.clone());
+                }
+            }
+        }
+        _ => tokens.push(arg.to_string()),
+    }
+}
+
+// Lists the known keys that parse into the given variant
+fn keys_of<T: ArgumentType + PartialEq>(variant: &T) -> Vec<&'static str> {
+    T::known_keys()
+        .iter()
+        .copied()
+        .filter(|key| matches!(T::from_str(key, ParseError::UnknownFlag), Ok(ref v) if v == variant))
+        .collect()
+}
+
+// Writes one indented line of help text, skipping variants without keys
+fn push_usage_line(usage: &mut String, keys: &[&str], description: &str, color: bool) {
+    if !keys.is_empty() {
+        let keys: Vec<String> = keys.iter().map(|key| color::paint(key, color::BOLD, color)).collect();
+        usage.push_str("  ");
+        usage.push_str(&keys.join(", "));
+        if !description.is_empty() {
+            usage.push_str("  ");
+            usage.push_str(description);
+        }
+        usage.push('\n');
+    }
+}
+
+// Looks up a flag key, falling back to the long key it abbreviates if
+// `ParseOptions::allow_abbreviation` is set
+fn lookup_flag<F: ArgumentType, E: Error>(
+    token: &str,
+    value: Option<&str>,
+    error: E,
+    options: &ParseOptions,
+) -> Result<F, E> {
+    match lookup_exact(token, value, error, options) {
+        Err(e) if options.allows_abbreviation() => {
+            match abbreviation_candidates::<F>(token, value, options).as_slice() {
+                [key] => lookup_exact(key, value, e, options),
+                _ => Err(e),
+            }
+        }
+        result => result,
+    }
+}
+
+// Lists the long keys that start with an abbreviated one, keeping only the
+// first key of each flag so that aliases of one flag aren't ambiguous. A
+// bare `--` abbreviates nothing.
+fn abbreviation_candidates<F: ArgumentType>(
+    token: &str,
+    value: Option<&str>,
+    options: &ParseOptions,
+) -> Vec<&'static str> {
+    let mut candidates: Vec<(&'static str, mem::Discriminant<F>)> = Vec::new();
+    if token.len() <= 2 || !token.starts_with("--") {
+        return Vec::new();
+    }
+    for &key in F::known_keys().iter().filter(|key| key.starts_with(token)) {
+        if let Ok(flag) = lookup_exact::<F, _>(key, value, ParseError::UnknownFlag, options) {
+            let variant = mem::discriminant(&flag);
+            if candidates.iter().all(|&(_, seen)| seen != variant) {
+                candidates.push((key, variant));
+            }
+        }
+    }
+    candidates.into_iter().map(|(key, _)| key).collect()
+}
+
+// Splits a token like `-Dname=value` into a map flag and its value, if its
+// first two characters are a map flag's key. With attached values enabled,
+// `-ofile` is split the same way for any flag that takes a value.
+fn attached_flag<'a, F: ArgumentType, E: Error>(
+    arg: &'a str,
+    error: E,
+    options: &ParseOptions,
+) -> Option<(F, &'a str)> {
+    if arg.starts_with("--") {
+        return None;
+    }
+    let split = arg.char_indices().nth(2)?.0;
+    let key: F = lookup_exact(&arg[..split], None, error, options).ok()?;
+    if key.is_map() || (options.attaches_values() && key.takes_value()) {
+        Some((key, &arg[split..]))
+    } else {
+        None
+    }
+}
+
+// Looks up a flag key as written, or the key it's an alias of, handing it
+// to `ArgumentType::from_key` without its dashes if
+// `ParseOptions::strip_prefix` is set
+fn lookup_exact<F: ArgumentType, E: Error>(
+    token: &str,
+    value: Option<&str>,
+    error: E,
+    options: &ParseOptions,
+) -> Result<F, E> {
+    let token = F::aliases()
+        .iter()
+        .find(|&&(alias, _)| alias == token)
+        .map_or(token, |&(_, canonical)| canonical);
+    if !options.strips_prefix() {
+        return F::from_str_with_value(token, value, error);
+    }
+    match token.strip_prefix("--") {
+        Some(key) => F::from_key(key, KeyStyle::Long, error),
+        None => F::from_key(&token[1..], KeyStyle::Short, error),
+    }
+}
+
+// Writes a JSON array of strings
+fn push_json_strings<'s>(json: &mut String, items: impl Iterator<Item = &'s str>) {
+    json.push('[');
+    for (i, item) in items.enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        push_json_string(json, item);
+    }
+    json.push(']');
+}
+
+// Checks whether a token like `-vh` is made up entirely of known short flags
+fn is_known_cluster<F: ArgumentType>(arg: &str) -> bool {
+    let keys = F::known_keys();
+    !arg.starts_with("--")
+        && arg.chars().count() > 2
+        && arg.chars().skip(1).all(|c| {
+            keys.iter().any(|key| {
+                let mut chars = key.chars();
+                chars.next() == Some('-') && chars.next() == Some(c) && chars.next().is_none()
+            })
+        })
+}
+
+// Writes a string to JSON output, quoted and escaped
+fn push_json_string(json: &mut String, value: &str) {
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
     }
+    json.push('"');
 }
@@ -0,0 +1,212 @@
+/// Declares a flag or action enum along with its `ArgumentType` impl, for
+/// those who'd rather not write `from_str` by hand.
+///
+/// Each variant lists the keys it's written as. The generated impl covers
+/// `from_str`, `known_keys`, `variants`, `canonical_key` (the last key
+/// listed), `describe` (the variant's doc comment) and `bit` (the variant's
+/// position). Attributes such as
+/// `#[derive(..)]` are passed on to the enum.
+///
+/// # Examples
+///
+/// ```
+/// use adante::{arg_enum, ArgumentType, ParseError};
+///
+/// arg_enum! {
+///     #[derive(Debug, Clone, Copy, PartialEq)]
+///     pub enum FlagType {
+///         /// Print this help
+///         Help => ["-h", "--help"],
+///         Verbose => ["-v", "--verbose"],
+///     }
+/// }
+///
+/// assert_eq!(FlagType::from_str("--help", ParseError::UnknownFlag), Ok(FlagType::Help));
+/// assert_eq!(FlagType::known_keys(), &["-h", "--help", "-v", "--verbose"]);
+/// assert_eq!(FlagType::Verbose.canonical_key(), "--verbose");
+/// assert_eq!(FlagType::Help.describe(), "Print this help");
+/// ```
+#[macro_export]
+macro_rules! arg_enum {
+    (@last $key:literal) => {
+        $key
+    };
+    (@last $first:literal, $($rest:literal),+) => {
+        $crate::arg_enum!(@last $($rest),+)
+    };
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $(
+                $(#[doc = $doc:expr])*
+                $variant:ident => [$($key:literal),+ $(,)?]
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis enum $name {
+            $(
+                $(#[doc = $doc])*
+                $variant,
+            )*
+        }
+
+        impl $crate::ArgumentType for $name {
+            fn from_str<E: $crate::Error>(key: &str, error: E) -> Result<Self, E> {
+                match key {
+                    $($($key)|+ => Ok(Self::$variant),)*
+                    _ => Err(error),
+                }
+            }
+            fn known_keys() -> &'static [&'static str] {
+                &[$($($key),+),*]
+            }
+            fn canonical_key(&self) -> &'static str {
+                match self {
+                    $(Self::$variant => $crate::arg_enum!(@last $($key),+),)*
+                }
+            }
+            fn describe(&self) -> &'static str {
+                match self {
+                    $(Self::$variant => concat!("" $(, $doc)*).trim(),)*
+                }
+            }
+            fn variants() -> &'static [Self] {
+                &[$(Self::$variant),*]
+            }
+            fn bit(&self) -> u32 {
+                match self {
+                    $(Self::$variant => Self::$variant as u32,)*
+                }
+            }
+        }
+    };
+}
+
+/// Declares an error enum along with its `Error` impl, pairing each variant
+/// with its message.
+///
+/// The generated `handle` prints the message to stderr, as `error: <msg>`,
+/// and exits with the variant's exit code: 1 unless one is given with
+/// `(code = ..)`. Without the `std` feature it panics with the message
+/// instead. The enum also gets `render` and `exit_code` methods returning
+/// what `handle` would print and exit with, along with `Display` and, with
+/// the `std` feature, `std::error::Error` impls. Attributes such as
+/// `#[derive(..)]` are passed on to the enum, which must derive `Debug`.
+///
+/// # Examples
+///
+/// ```
+/// use adante::{error_enum, Error};
+///
+/// error_enum! {
+///     #[derive(Debug, Clone, Copy, PartialEq)]
+///     pub enum ErrorType {
+///         Syntax(code = 2) => "Improper syntax usage",
+///         UnknownFlag => "Unknown flag",
+///     }
+/// }
+///
+/// assert_eq!(ErrorType::UnknownFlag.as_str(), "Unknown flag");
+/// assert_eq!(ErrorType::Syntax.render(), "error: Improper syntax usage");
+/// assert_eq!(ErrorType::Syntax.exit_code(), 2);
+/// assert_eq!(ErrorType::UnknownFlag.exit_code(), 1);
+/// ```
+#[macro_export]
+macro_rules! error_enum {
+    (@code) => {
+        1
+    };
+    (@code $code:expr) => {
+        $code
+    };
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $(
+                $(#[doc = $doc:expr])*
+                $variant:ident $((code = $code:expr))? => $message:expr
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis enum $name {
+            $(
+                $(#[doc = $doc])*
+                $variant,
+            )*
+        }
+
+        impl $name {
+            /// Returns the line `handle` prints.
+            $vis fn render(&self) -> $crate::__private::String {
+                $crate::__private::render_error(self.as_str())
+            }
+            /// Returns the code `handle` exits with.
+            $vis fn exit_code(&self) -> i32 {
+                match self {
+                    $(Self::$variant => $crate::error_enum!(@code $($code)?),)*
+                }
+            }
+        }
+
+        impl $crate::Error for $name {
+            fn handle(&self) {
+                $crate::__private::exit_with_error(&self.render(), self.exit_code());
+            }
+            fn as_str(&self) -> &str {
+                match self {
+                    $(Self::$variant => $message,)*
+                }
+            }
+        }
+
+        impl ::core::fmt::Display for $name {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                f.write_str($crate::Error::as_str(self))
+            }
+        }
+
+        $crate::__impl_std_error!($name);
+    };
+}
+
+#[cfg(feature = "std")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __impl_std_error {
+    ($name:ident) => {
+        impl ::std::error::Error for $name {}
+    };
+}
+
+#[cfg(not(feature = "std"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __impl_std_error {
+    ($name:ident) => {};
+}
+
+// Support for the code generated by the macros above
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::string::String;
+    use alloc::format;
+
+    pub fn render_error(message: &str) -> String {
+        format!("error: {}", message)
+    }
+
+    pub fn exit_with_error(rendered: &str, code: i32) {
+        #[cfg(feature = "std")]
+        {
+            std::eprintln!("{}", rendered);
+            std::process::exit(code);
+        }
+        #[cfg(not(feature = "std"))]
+        {
+            let _ = code;
+            panic!("{}", rendered);
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use crate::ParseError;
+
+/// The text adante writes around errors and help, which a translated
+/// catalog can replace through `ParseOptions::messages`.
+///
+/// `Messages::ENGLISH` is the text used when no catalog is set, so a
+/// translation only needs to override the fields it covers. Declaring it as
+/// a `const` or `static` gives the `'static` reference the options take.
+///
+/// # Examples
+///
+/// ```
+/// use adante::{Messages, ParseError, ParseOptions};
+///
+/// const GERMAN: Messages = Messages {
+///     unknown_flag: "Unbekannte Option",
+///     ..Messages::ENGLISH
+/// };
+/// let options = ParseOptions::new().messages(&GERMAN);
+///
+/// assert_eq!(GERMAN.parse_error(&ParseError::UnknownFlag), "Unbekannte Option");
+/// assert_eq!(GERMAN.parse_error(&ParseError::EmptyArg), "Empty argument");
+/// assert_eq!(options.message_catalog().argument, "argument");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Messages {
+    /// Starts a rendered failure, `"error: "`
+    pub error_prefix: &'static str,
+    /// Names the offending argument of a failure, `"argument"`
+    pub argument: &'static str,
+    /// Names the offending line of a failure from a config file, `"line"`
+    pub line: &'static str,
+    /// Introduces the keys an ambiguous abbreviation could stand for,
+    /// `"did you mean"`
+    pub did_you_mean: &'static str,
+    /// Heads the flags in the help text, `"Flags:"`
+    pub flags_heading: &'static str,
+    /// Heads the actions in the help text, `"Actions:"`
+    pub actions_heading: &'static str,
+    /// The message of `ParseError::UnknownFlag`, `"Unknown flag"`
+    pub unknown_flag: &'static str,
+    /// The message of `ParseError::UnknownAction`, `"Unknown action"`
+    pub unknown_action: &'static str,
+    /// The message of `ParseError::MissingValue`, `"Missing value for flag"`
+    pub missing_value: &'static str,
+    /// The message of `ParseError::UnexpectedValue`,
+    /// `"Flag does not take a value"`
+    pub unexpected_value: &'static str,
+    /// The message of `ParseError::EmptyArg`, `"Empty argument"`
+    pub empty_arg: &'static str,
+}
+
+impl Messages {
+    /// The English catalog, the same as `Messages::default()`.
+    pub const ENGLISH: Messages = Messages {
+        error_prefix: "error: ",
+        argument: "argument",
+        line: "line",
+        did_you_mean: "did you mean",
+        flags_heading: "Flags:",
+        actions_heading: "Actions:",
+        unknown_flag: "Unknown flag",
+        unknown_action: "Unknown action",
+        missing_value: "Missing value for flag",
+        unexpected_value: "Flag does not take a value",
+        empty_arg: "Empty argument",
+    };
+
+    /// Returns the catalog's message for a `ParseError`.
+    pub fn parse_error(&self, error: &ParseError) -> &'static str {
+        match error {
+            ParseError::UnknownFlag => self.unknown_flag,
+            ParseError::UnknownAction => self.unknown_action,
+            ParseError::MissingValue => self.missing_value,
+            ParseError::UnexpectedValue => self.unexpected_value,
+            ParseError::EmptyArg => self.empty_arg,
+        }
+    }
+}
+
+impl Default for Messages {
+    fn default() -> Self {
+        Self::ENGLISH
+    }
+}
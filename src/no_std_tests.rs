@@ -0,0 +1,62 @@
+// Exercises the parser using only `core` and `alloc`, without the std prelude
+// or any std-gated helpers. Under `cargo test --no-default-features` the crate
+// is no_std, so this module proves the parser works there.
+
+use alloc::string::ToString;
+use alloc::vec;
+
+use crate::{ArgumentType, Arguments, ParseError, ParseOptions};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FlagType {
+    Help,
+    Verbose,
+}
+impl ArgumentType for FlagType {
+    fn from_str<ErrorType>(key: &str, error: ErrorType) -> Result<Self, ErrorType> {
+        match key {
+            "-h" | "--help" => Ok(Self::Help),
+            "-v" | "--verbose" => Ok(Self::Verbose),
+            _ => Err(error),
+        }
+    }
+    fn known_keys() -> &'static [&'static str] {
+        &["-h", "--help", "-v", "--verbose"]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ActionType {
+    Add,
+}
+impl ArgumentType for ActionType {
+    fn from_str<ErrorType>(key: &str, error: ErrorType) -> Result<Self, ErrorType> {
+        match key {
+            "add" | "a" => Ok(Self::Add),
+            _ => Err(error),
+        }
+    }
+}
+
+#[test]
+fn parse_without_std() {
+    let env_args: Arguments<FlagType, ActionType> =
+        Arguments::parse(vec!["-h=test", "add"], ParseError::UnknownFlag).unwrap();
+    assert_eq!(env_args.flags[0].value, Some("test".to_string()));
+    assert_eq!(env_args.actions[0], ActionType::Add);
+}
+
+#[test]
+fn parse_with_options_without_std() {
+    let options = ParseOptions::new().short_clusters(true).strip_quotes(true);
+    let env_args: Arguments<FlagType, ActionType> =
+        Arguments::parse_with(vec!["-vh", "--help='x'", "add"], ParseError::UnknownFlag, &options).unwrap();
+    assert_eq!(env_args.summary(), "flags=[Verbose, Help, Help=x] actions=[Add]");
+}
+
+#[test]
+fn report_errors_without_std() {
+    let result: Result<Arguments<FlagType, ActionType>, _> =
+        Arguments::parse(vec!["add", "--colour"], ParseError::EmptyArg);
+    assert_eq!(result.unwrap_err(), ParseError::UnknownFlag);
+}
@@ -0,0 +1,442 @@
+use crate::{Messages, Warning};
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Describes what `Arguments::parse_with_dedup` does when the same flag key
+/// is passed more than once.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DuplicatePolicy {
+    /// Keep every occurrence (the default, and the behavior of `parse`).
+    #[default]
+    Allow,
+    /// Fail on the second occurrence of a key.
+    Error,
+    /// Keep the first occurrence and drop the rest.
+    FirstWins,
+    /// Keep the last occurrence and drop the earlier ones.
+    LastWins,
+}
+
+/// Describes how many actions `Arguments::parse_with` accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ActionPolicy {
+    /// Any number of actions, including none (the default).
+    #[default]
+    Any,
+    /// No more than one action.
+    AtMostOne,
+    /// Exactly one action; none at all is also an error.
+    ExactlyOne,
+}
+
+/// The number of positional arguments `Arguments::parse_with` accepts.
+///
+/// The default accepts any number.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PositionalPolicy {
+    /// The fewest positional arguments allowed
+    pub min: usize,
+    /// The most positional arguments allowed, if there is a limit
+    pub max: Option<usize>,
+}
+
+/// A set of switches that change how `Arguments::parse_with` treats its
+/// input. The defaults reproduce the behavior of `Arguments::parse`.
+///
+/// # Examples
+/// ```
+/// use adante::{DuplicatePolicy, ParseOptions};
+///
+/// let options = ParseOptions::new().duplicate_flags(DuplicatePolicy::Error);
+///
+/// assert_eq!(options.duplicates(), DuplicatePolicy::Error);
+/// assert_eq!(ParseOptions::new().duplicates(), DuplicatePolicy::Allow);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    duplicate_flags: DuplicatePolicy,
+    action_policy: ActionPolicy,
+    strip_quotes: bool,
+    short_clusters: bool,
+    response_files: bool,
+    greedy_values: bool,
+    strip_prefix: bool,
+    negation_prefix: Option<String>,
+    separator: Option<char>,
+    slash_flags: bool,
+    dashless_flags: bool,
+    posix_mode: bool,
+    passthrough_pairs: bool,
+    abbreviation: bool,
+    map_pairs: bool,
+    require_values: bool,
+    positionals: Option<PositionalPolicy>,
+    handle_errors: bool,
+    attached_values: bool,
+    aliases: Vec<(String, Vec<String>)>,
+    alias_depth: Option<usize>,
+    on_warning: Option<WarningHook>,
+    messages: Option<&'static Messages>,
+}
+
+// The hook set with `ParseOptions::on_warning`, shared between clones
+#[derive(Clone)]
+struct WarningHook(Arc<dyn Fn(Warning) + Send + Sync>);
+
+impl fmt::Debug for WarningHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("WarningHook")
+    }
+}
+
+impl ParseOptions {
+    /// A default constructor for the ParseOptions type.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Sets the policy applied to repeated flag keys.
+    ///
+    /// Comparing keys needs `F: PartialEq`, so the policy is only applied by
+    /// `Arguments::parse_with_dedup`; `Arguments::parse_with` always allows
+    /// duplicates.
+    pub fn duplicate_flags(mut self, policy: DuplicatePolicy) -> Self {
+        self.duplicate_flags = policy;
+        self
+    }
+    /// Returns the policy applied to repeated flag keys.
+    pub fn duplicates(&self) -> DuplicatePolicy {
+        self.duplicate_flags
+    }
+    /// Sets how many actions are accepted, checked once parsing is done.
+    ///
+    /// A second action is reported at its own index; a missing action under
+    /// `ActionPolicy::ExactlyOne` is reported one past the last argument with
+    /// an empty token.
+    pub fn action_policy(mut self, policy: ActionPolicy) -> Self {
+        self.action_policy = policy;
+        self
+    }
+    /// Returns the policy applied to the number of actions.
+    pub fn actions(&self) -> ActionPolicy {
+        self.action_policy
+    }
+    /// Sets whether flag values wrapped in quotes are unquoted, so that
+    /// `--name="bob"` gets the value `bob`. Off by default.
+    ///
+    /// Only a single balanced pair of leading and trailing `"` or `'` is
+    /// removed; `--name="'bob'"` gets the value `'bob'`.
+    pub fn strip_quotes(mut self, strip: bool) -> Self {
+        self.strip_quotes = strip;
+        self
+    }
+    /// Returns whether flag values are unquoted.
+    pub fn strips_quotes(&self) -> bool {
+        self.strip_quotes
+    }
+    /// Sets whether an unrecognized token like `-vh` is expanded into the
+    /// short flags `-v` and `-h`. Off by default.
+    ///
+    /// A token is only expanded if every one of its letters is a known
+    /// short flag according to `ArgumentType::known_keys`; otherwise it is
+    /// reported as a single unknown flag, so `-file` is never mistaken for
+    /// `-f -i -l -e` unless all four exist.
+    pub fn short_clusters(mut self, expand: bool) -> Self {
+        self.short_clusters = expand;
+        self
+    }
+    /// Returns whether short flag clusters are expanded.
+    pub fn expands_short_clusters(&self) -> bool {
+        self.short_clusters
+    }
+    /// Sets whether a short flag that takes a value can have it attached
+    /// directly, so that `-ofile` is read as `-o` with the value `file`.
+    /// Off by default.
+    ///
+    /// This is checked before `short_clusters`, so `-ov` is `-o` with the
+    /// value `v` whenever `-o` takes a value (see
+    /// `ArgumentType::takes_value`).
+    pub fn attached_values(mut self, attach: bool) -> Self {
+        self.attached_values = attach;
+        self
+    }
+    /// Returns whether short flags can have their value attached.
+    pub fn attaches_values(&self) -> bool {
+        self.attached_values
+    }
+    /// Sets aliases that replace an argument with one or more others before
+    /// it is parsed, such as `("co", &["checkout"])`.
+    ///
+    /// Only arguments in the place of an action are replaced: never flags,
+    /// their values (including those collected by `greedy_values`), or
+    /// anything after `--`. An expansion is itself expanded up to the
+    /// depth set with `alias_depth`.
+    ///
+    /// # Examples
+    /// ```
+    /// use adante::ParseOptions;
+    ///
+    /// let options = ParseOptions::new().aliases(&[("co", &["checkout"]), ("pu", &["push", "-f"])]);
+    ///
+    /// assert_eq!(options.alias("pu"), Some(&["push".to_string(), "-f".to_string()][..]));
+    /// assert_eq!(options.alias("push"), None);
+    /// ```
+    pub fn aliases(mut self, aliases: &[(&str, &[&str])]) -> Self {
+        self.aliases = aliases
+            .iter()
+            .map(|(name, expansion)| {
+                (name.to_string(), expansion.iter().map(|arg| arg.to_string()).collect())
+            })
+            .collect();
+        self
+    }
+    /// Returns the expansion of an alias, if `name` is one.
+    pub fn alias(&self, name: &str) -> Option<&[String]> {
+        self.aliases
+            .iter()
+            .find(|(alias, _)| alias == name)
+            .map(|(_, expansion)| expansion.as_slice())
+    }
+    /// Sets how many times an alias's expansion may itself be expanded.
+    /// Defaults to 1, so an alias is replaced once and its expansion is
+    /// left as it is.
+    pub fn alias_depth(mut self, depth: usize) -> Self {
+        self.alias_depth = Some(depth);
+        self
+    }
+    /// Returns how many times aliases are expanded.
+    pub fn max_alias_depth(&self) -> usize {
+        self.alias_depth.unwrap_or(1)
+    }
+    /// Returns whether any aliases are set.
+    pub fn has_aliases(&self) -> bool {
+        !self.aliases.is_empty()
+    }
+    /// Sets whether an `@path` argument is replaced by the whitespace
+    /// separated arguments read from the file at `path`. Off by default.
+    ///
+    /// Response files may name other response files, up to four levels
+    /// deep. A file that can't be read, or one nested too deeply, fails with
+    /// the `@path` argument as the `ParseFailure` token. The index of any
+    /// failure is that of the argument the offending token was read from.
+    ///
+    /// Requires the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn expand_response_files(mut self, expand: bool) -> Self {
+        self.response_files = expand;
+        self
+    }
+    /// Returns whether response files are expanded.
+    pub fn expands_response_files(&self) -> bool {
+        self.response_files
+    }
+    /// Sets whether a flag written without `=` collects every following
+    /// argument up to the next flag into `Flag::values`, so that
+    /// `--files a b -v` gives `--files` the values `a` and `b`. Off by
+    /// default.
+    ///
+    /// Only flags whose `ArgumentType::takes_value` returns `true` collect
+    /// values; the arguments after any other flag are still actions.
+    pub fn greedy_values(mut self, greedy: bool) -> Self {
+        self.greedy_values = greedy;
+        self
+    }
+    /// Returns whether flags collect the arguments that follow them.
+    pub fn collects_greedy_values(&self) -> bool {
+        self.greedy_values
+    }
+    /// Sets whether keys are looked up with `ArgumentType::from_key`, with
+    /// their dashes removed and a `KeyStyle` saying which prefix they had,
+    /// instead of with `ArgumentType::from_str`. Off by default.
+    ///
+    /// In this mode `ArgumentType::from_str_with_value` isn't called.
+    pub fn strip_prefix(mut self, strip: bool) -> Self {
+        self.strip_prefix = strip;
+        self
+    }
+    /// Returns whether keys are looked up without their dashes.
+    pub fn strips_prefix(&self) -> bool {
+        self.strip_prefix
+    }
+    /// Sets a prefix, such as `"no-"`, that negates a long flag: when
+    /// `--no-color` isn't a flag itself, it is parsed as `--color` with the
+    /// value `"false"`. Off by default.
+    ///
+    /// A key that matches directly always wins, so a real `--no-cache` flag
+    /// is never rewritten. The value is stored even if the flag's
+    /// `ArgumentType::takes_value` returns `false`.
+    pub fn negation_prefix(mut self, prefix: &str) -> Self {
+        self.negation_prefix = Some(prefix.to_string());
+        self
+    }
+    /// Returns the prefix that negates a long flag, if any.
+    pub fn negation(&self) -> Option<&str> {
+        self.negation_prefix.as_deref()
+    }
+    /// Sets the character that separates a flag from its value, such as
+    /// `:` for `--timeout:30`. Defaults to `=`.
+    pub fn value_separator(mut self, separator: char) -> Self {
+        self.separator = Some(separator);
+        self
+    }
+    /// Returns the character that separates a flag from its value.
+    pub fn separator(&self) -> char {
+        self.separator.unwrap_or('=')
+    }
+    /// Sets whether `/` marks a flag, as on Windows, so that `/verbose` and
+    /// `/out:file` parse like `--verbose` and `--out=file`, and `/v` like
+    /// `-v`. A value follows a `:`. Off by default.
+    ///
+    /// An argument is only rewritten if the key it becomes is a known flag,
+    /// so absolute paths such as `/tmp` are left alone.
+    pub fn slash_flags(mut self, allow: bool) -> Self {
+        self.slash_flags = allow;
+        self
+    }
+    /// Returns whether `/` marks a flag.
+    pub fn allows_slash_flags(&self) -> bool {
+        self.slash_flags
+    }
+    /// Sets whether an unknown long flag like `--verb` is accepted as an
+    /// abbreviation of the one long key in `ArgumentType::known_keys` that
+    /// starts with it, such as `--verbose`. Off by default.
+    ///
+    /// A key that matches exactly always wins. A prefix shared by the keys
+    /// of several flags is ambiguous and fails like any unknown flag, with
+    /// the abbreviation as the `ParseFailure` token and the keys it could
+    /// stand for as its `candidates`. A bare `--` is never an abbreviation.
+    pub fn allow_abbreviation(mut self, allow: bool) -> Self {
+        self.abbreviation = allow;
+        self
+    }
+    /// Returns whether long flags may be abbreviated.
+    pub fn allows_abbreviation(&self) -> bool {
+        self.abbreviation
+    }
+    /// Sets whether a map flag's value must contain `=`, so that `-Dname`
+    /// fails instead of defining `name` as an empty string. Off by default.
+    pub fn require_map_pairs(mut self, require: bool) -> Self {
+        self.map_pairs = require;
+        self
+    }
+    /// Returns whether a map flag's value must contain `=`.
+    pub fn requires_map_pairs(&self) -> bool {
+        self.map_pairs
+    }
+    /// Sets whether a flag that takes a value (see
+    /// `ArgumentType::takes_value`) fails when passed without one, rather
+    /// than being recorded with no value, with the error from
+    /// `Error::missing_value`. Values collected with `greedy_values` count,
+    /// so a `Parser` left with such a flag still waiting for them fails in
+    /// `finish`. Off by default.
+    pub fn require_values(mut self, require: bool) -> Self {
+        self.require_values = require;
+        self
+    }
+    /// Returns whether a flag that takes a value fails without one.
+    pub fn requires_values(&self) -> bool {
+        self.require_values
+    }
+    /// Sets how many positional arguments are accepted. Off by default.
+    ///
+    /// Once set, an argument that isn't a flag or a known action is kept in
+    /// `Arguments::positionals` instead of failing, and so is everything
+    /// after a `--` argument. Too many positionals fail at the first extra
+    /// one; too few fail one past the last argument with an empty token.
+    /// Either way the error comes from `Error::positional_count`, which is
+    /// told how many were found.
+    pub fn positionals(mut self, policy: PositionalPolicy) -> Self {
+        self.positionals = Some(policy);
+        self
+    }
+    /// Returns the positional argument policy, if one is set.
+    pub fn positional_policy(&self) -> Option<PositionalPolicy> {
+        self.positionals
+    }
+    /// Sets whether the parser calls `Error::handle_with` on a failure,
+    /// with the offending argument, before returning the error. Off by
+    /// default.
+    pub fn handle_errors(mut self, handle: bool) -> Self {
+        self.handle_errors = handle;
+        self
+    }
+    /// Returns whether the parser calls `Error::handle_with` on a failure.
+    pub fn handles_errors(&self) -> bool {
+        self.handle_errors
+    }
+    /// Sets whether the arguments after `--` are collected into
+    /// `Arguments::passthrough` as `key=value` pairs for another tool, split
+    /// at the value separator, rather than parsed. Off by default.
+    ///
+    /// This takes precedence over `positionals` and `posix_mode` for what
+    /// comes after `--`, unless POSIX mode has already made it an operand.
+    /// An argument there without the separator fails with the error given
+    /// to the parse.
+    pub fn passthrough_pairs(mut self, collect: bool) -> Self {
+        self.passthrough_pairs = collect;
+        self
+    }
+    /// Returns whether the arguments after `--` are collected as
+    /// `key=value` pairs.
+    pub fn collects_passthrough_pairs(&self) -> bool {
+        self.passthrough_pairs
+    }
+    /// Sets the catalog of text used to render failures from the parse and
+    /// the help text from `Arguments::usage_for`, for translating them.
+    /// Defaults to `Messages::ENGLISH`.
+    pub fn messages(mut self, messages: &'static Messages) -> Self {
+        self.messages = Some(messages);
+        self
+    }
+    /// Returns the catalog of text used for rendering.
+    pub fn message_catalog(&self) -> &'static Messages {
+        self.messages.unwrap_or(&Messages::ENGLISH)
+    }
+    /// Sets whether an argument such as `name=bob`, with no dashes, parses
+    /// like `--name=bob`. Off by default.
+    ///
+    /// An argument is only rewritten if it contains the value separator and
+    /// the key before it is a known long flag, in which case the flag takes
+    /// precedence over an action of the same name.
+    pub fn dashless_flags(mut self, allow: bool) -> Self {
+        self.dashless_flags = allow;
+        self
+    }
+    /// Returns whether `name=value` parses like `--name=value`.
+    pub fn allows_dashless_flags(&self) -> bool {
+        self.dashless_flags
+    }
+    /// Sets whether flags are only recognized before the first action or
+    /// positional argument, as when `POSIXLY_CORRECT` is set. Everything
+    /// after it is kept in `Arguments::positionals`, even if it starts with
+    /// `-`, as is everything after `--`. Off by default, so flags and
+    /// actions can be mixed freely.
+    pub fn posix_mode(mut self, posix: bool) -> Self {
+        self.posix_mode = posix;
+        self
+    }
+    /// Returns whether flags are only recognized before the first action or
+    /// positional argument.
+    pub fn is_posix(&self) -> bool {
+        self.posix_mode
+    }
+    /// Sets a hook called with a `Warning` for every argument that parses
+    /// but is flagged by `ArgumentType::deprecated`. The argument is still
+    /// recorded as usual. None by default.
+    ///
+    /// The hook is called once the arguments have been read, in order, even
+    /// if a later argument fails to parse. It must be `Send` and `Sync` so
+    /// that the options can be shared between threads, so one that collects
+    /// warnings needs a `Mutex` or similar.
+    pub fn on_warning<W: Fn(Warning) + Send + Sync + 'static>(mut self, hook: W) -> Self {
+        self.on_warning = Some(WarningHook(Arc::new(hook)));
+        self
+    }
+    // Passes a warning to the hook, if there is one
+    pub(crate) fn warn(&self, warning: Warning) {
+        if let Some(WarningHook(hook)) = &self.on_warning {
+            hook(warning);
+        }
+    }
+}
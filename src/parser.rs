@@ -0,0 +1,666 @@
+use crate::recognize::{convert_flag, Item, Recognizer};
+use crate::{ArgumentType, Arguments, Error, Expander, Flag, ParseError, ParseFailure, ParseOptions};
+use alloc::collections::VecDeque;
+use alloc::string::{String, ToString};
+use alloc::vec::{self, Vec};
+use core::iter::FromIterator;
+use core::marker::PhantomData;
+use core::ops::Range;
+
+/// A single recognized argument, as yielded by `Arguments::parse_iter`
+/// and `Parser::push`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParsedItem<F, A> {
+    /// A flag, with its value if one was given
+    Flag(Flag<F>),
+    /// An action
+    Action(A),
+    /// An argument that is neither a flag nor an action
+    Positional(String),
+    /// A `key=value` pair after `--`, collected when
+    /// `ParseOptions::passthrough_pairs` is set
+    Passthrough(String, String),
+}
+
+impl<F, A> ParsedItem<F, A> {
+    // Takes ownership of an item borrowed from the arguments
+    fn from_item(item: Item<'_, F, A>) -> Self {
+        match item {
+            Item::Flag(flag) => ParsedItem::Flag(convert_flag(flag)),
+            Item::Action(action) => ParsedItem::Action(action),
+            Item::Positional(value) => ParsedItem::Positional(value.to_string()),
+            Item::Pair(key, value) => ParsedItem::Passthrough(key.to_string(), value.to_string()),
+        }
+    }
+}
+
+/// The iterator returned by `Arguments::parse_iter`.
+///
+/// It stops after yielding the first error.
+pub struct ParseIter<I: Iterator, F, A, E> {
+    tokens: I,
+    stream: Stream<F, A, E>,
+    options: ParseOptions,
+    // The items recognized but not yielded yet, then the failure that
+    // ended the parse, if any
+    ready: VecDeque<ParsedItem<F, A>>,
+    failure: Option<ParseFailure<E>>,
+    done: bool,
+}
+
+impl<'a, I, F, A, E> Iterator for ParseIter<I, F, A, E>
+where
+    I: Iterator<Item = &'a str>,
+    F: ArgumentType,
+    A: ArgumentType,
+    E: Error + Clone,
+{
+    type Item = Result<ParsedItem<F, A>, ParseFailure<E>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.ready.pop_front() {
+                return Some(Ok(item));
+            }
+            if let Some(failure) = self.failure.take() {
+                return Some(Err(failure));
+            }
+            if self.done {
+                return None;
+            }
+            let ready = &mut self.ready;
+            let mut sink = |item| {
+                ready.push_back(item);
+                true
+            };
+            // Read arguments until one completes an item:
+            let result = match self.tokens.next() {
+                Some(token) => self.stream.push(token, &self.options, &mut sink),
+                None => {
+                    self.done = true;
+                    self.stream.finish(&self.options, &mut sink)
+                }
+            };
+            if let Err(failure) = result {
+                self.done = true;
+                self.failure = Some(failure);
+            }
+        }
+    }
+}
+
+/// A parser fed one argument at a time, for interactive shells that
+/// receive them as they're typed. It recognizes arguments the same way as
+/// `Arguments::parse_with` given the same options.
+///
+/// An argument may have to wait for the ones after it before it can be
+/// recognized: a flag collecting `ParseOptions::greedy_values` waits for
+/// the next flag, and an action that `ArgumentType::from_str` rejects may
+/// still be one that carries data (see `ArgumentType::from_str_with_arg`).
+/// Action types that list their keys in `ArgumentType::known_keys` only
+/// wait for those keys, so any other word fails as soon as it's pushed.
+///
+/// # Examples
+///
+/// ```
+/// use adante::{ArgumentType, Error, ParseError, ParsedItem, Parser};
+///
+/// #[derive(Debug, Clone, Copy, PartialEq)]
+/// enum FlagType {
+///     Verbose,
+/// }
+/// impl ArgumentType for FlagType {
+///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+///                                -> Result<Self, ErrorType> {
+///         match key {
+///             "-v" | "--verbose" => Ok(Self::Verbose),
+///             _ => Err(error),
+///         }
+///     }
+/// }
+/// #[derive(Debug, Clone, Copy, PartialEq)]
+/// enum ActionType {
+///     Goto(usize),
+/// }
+/// impl ArgumentType for ActionType {
+///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+///         -> Result<Self, ErrorType> {
+///         Err(error)
+///     }
+///     fn from_str_with_arg<E: Error>(key: &str, arg: Option<&str>, error: E)
+///                                -> Result<Self, E> {
+///         match (key, arg.map(str::parse)) {
+///             ("goto", Some(Ok(line))) => Ok(Self::Goto(line)),
+///             _ => Err(error),
+///         }
+///     }
+/// }
+///
+/// let mut parser: Parser<FlagType, ActionType, _> = Parser::new(ParseError::UnknownAction);
+/// assert!(matches!(parser.push("-v").unwrap()[..], [ParsedItem::Flag(_)]));
+/// assert!(parser.push("goto").unwrap().is_empty());
+/// assert!(parser.is_waiting());
+/// assert_eq!(parser.push("5").unwrap(), vec![ParsedItem::Action(ActionType::Goto(5))]);
+///
+/// let args = parser.finish().unwrap();
+/// assert_eq!(args.actions, vec![ActionType::Goto(5)]);
+/// ```
+#[derive(Debug)]
+pub struct Parser<F, A, E> {
+    args: Arguments<F, A>,
+    stream: Stream<F, A, E>,
+    options: ParseOptions,
+    // Compares flag keys for the duplicate policy, if enabled
+    same_key: Option<fn(&F, &F) -> bool>,
+}
+
+impl<F, A, E> Parser<F, A, E>
+where
+    F: ArgumentType + Clone,
+    A: ArgumentType + Clone,
+    E: Error + Clone,
+{
+    /// A constructor for the Parser type, taking the error reported for
+    /// arguments that fail like the one passed to `Arguments::parse`.
+    pub fn new(error: E) -> Self {
+        Self::with_options(error, &ParseOptions::new())
+    }
+    /// Creates a parser that recognizes arguments as
+    /// `Arguments::parse_with` does with the given options. Like it, the
+    /// duplicate policy is left to `Parser::with_dedup`.
+    pub fn with_options(error: E, options: &ParseOptions) -> Self {
+        Parser {
+            args: Arguments::default(),
+            stream: Stream::new(error),
+            options: options.clone(),
+            same_key: None,
+        }
+    }
+    /// Creates a parser that also applies `ParseOptions::duplicate_flags`,
+    /// as `Arguments::parse_with_dedup` does. Flags the policy drops are
+    /// still returned by `push`, but left out of the result of `finish`.
+    pub fn with_dedup(error: E, options: &ParseOptions) -> Self
+    where
+        F: PartialEq,
+    {
+        Parser {
+            same_key: Some(F::eq),
+            ..Self::with_options(error, options)
+        }
+    }
+    /// Returns whether an argument pushed so far is waiting for the next
+    /// one before it can be recognized.
+    pub fn is_waiting(&self) -> bool {
+        self.stream.is_waiting()
+    }
+    /// Recognizes the next argument, returning the items it completes. The
+    /// list is empty if the argument has to wait for the ones after it, and
+    /// may hold several items, such as for a short flag cluster or an
+    /// argument that completes a waiting one.
+    ///
+    /// On failure, the `index` of the returned `ParseFailure` counts every
+    /// argument pushed so far, including the ones that failed, and any
+    /// argument still waiting is dropped.
+    pub fn push(&mut self, token: &str) -> Result<Vec<ParsedItem<F, A>>, ParseFailure<E>> {
+        let mut items = Vec::new();
+        let (args, options, same_key) = (&mut self.args, &self.options, self.same_key);
+        self.stream.push(token, options, &mut |item| {
+            let recorded = record(args, &item, options, same_key);
+            items.push(item);
+            recorded
+        })?;
+        Ok(items)
+    }
+    /// Returns the arguments recognized so far, failing if one that is
+    /// still waiting can't be recognized on its own, or if the action or
+    /// positional counts set in the options aren't met. A flag still
+    /// waiting for greedy values fails only if
+    /// `ParseOptions::require_values` is set.
+    pub fn finish(mut self) -> Result<Arguments<F, A>, ParseFailure<E>> {
+        let (args, options, same_key) = (&mut self.args, &self.options, self.same_key);
+        self.stream.finish(options, &mut |item| record(args, &item, options, same_key))?;
+        Ok(self.args)
+    }
+}
+
+// Adds a copy of an item to the arguments a `Parser` builds, returning
+// false if the duplicate policy rejects it
+fn record<F: ArgumentType + Clone, A: ArgumentType + Clone>(
+    args: &mut Arguments<F, A>,
+    item: &ParsedItem<F, A>,
+    options: &ParseOptions,
+    same_key: Option<fn(&F, &F) -> bool>,
+) -> bool {
+    match item {
+        ParsedItem::Flag(flag) => args.push_flag(flag.clone(), options, same_key),
+        item => {
+            args.push_item(item.clone());
+            true
+        }
+    }
+}
+
+// Feeds arguments one at a time through the expansion and recognition
+// that `Arguments::parse_with` does, holding on to those that have to wait
+// for the arguments after them
+#[derive(Debug)]
+struct Stream<F, A, E> {
+    recognizer: Recognizer<E>,
+    expander: Expander,
+    // The expanded arguments not recognized yet, each with the index and
+    // span of the argument it came from
+    pending: Vec<(usize, Range<usize>, String)>,
+    // The index of the next argument, and where it starts in the arguments
+    // joined with spaces
+    index: usize,
+    offset: usize,
+    items: PhantomData<fn() -> (F, A)>,
+}
+
+impl<F: ArgumentType, A: ArgumentType, E: Error + Clone> Stream<F, A, E> {
+    fn new(error: E) -> Self {
+        Stream {
+            recognizer: Recognizer::new(error),
+            expander: Expander::default(),
+            pending: Vec::new(),
+            index: 0,
+            offset: 0,
+            items: PhantomData,
+        }
+    }
+    fn is_waiting(&self) -> bool {
+        !self.pending.is_empty()
+    }
+    // Reads the next argument, handing each item it completes to `sink`,
+    // which returns false to reject it
+    fn push(
+        &mut self,
+        token: &str,
+        options: &ParseOptions,
+        sink: &mut dyn FnMut(ParsedItem<F, A>) -> bool,
+    ) -> Result<(), ParseFailure<E>> {
+        let index = self.index;
+        let span = self.offset..self.offset + token.len();
+        self.index += 1;
+        self.offset += token.len() + 1;
+        if Expander::is_needed(options) {
+            let mut tokens = Vec::new();
+            if let Err(bad) = self.expander.expand::<F>(token, options, &mut tokens) {
+                let failure = ParseFailure::new(self.recognizer.error.clone(), index, &bad);
+                return Err(self.fail(ParseFailure { span, ..failure }, options));
+            }
+            self.pending.extend(tokens.into_iter().map(|token| (index, span.clone(), token)));
+        } else {
+            self.pending.push((index, span, token.to_string()));
+        }
+        self.recognize(false, options, sink)
+    }
+    // Recognizes the arguments read so far, up to one that has to wait for
+    // more unless `end` says there are no more
+    fn recognize(
+        &mut self,
+        end: bool,
+        options: &ParseOptions,
+        sink: &mut dyn FnMut(ParsedItem<F, A>) -> bool,
+    ) -> Result<(), ParseFailure<E>> {
+        let mut warnings = Vec::new();
+        while let Some((index, span, token)) = self.pending.first().cloned() {
+            let step = {
+                let mut items = Vec::new();
+                let window: Vec<&str> = self.pending.iter().map(|(_, _, arg)| &arg[..]).collect();
+                let (at, found) = ((index, &span), &mut items);
+                let step = self.recognizer.step((&window, end), at, options, found, &mut warnings);
+                let items = items.into_iter().map(ParsedItem::from_item);
+                step.map(|used| used.map(|used| (used, items.collect::<Vec<_>>())))
+            };
+            for warning in warnings.drain(..) {
+                options.warn(warning);
+            }
+            let error = match step {
+                Ok(Some((used, items))) => {
+                    if items.into_iter().all(&mut *sink) {
+                        self.pending.drain(..used);
+                        continue;
+                    }
+                    self.recognizer.error.clone()
+                }
+                Ok(None) => return Ok(()),
+                Err(e) => e,
+            };
+            self.pending.clear();
+            let failure = ParseFailure::new(error, index, &token);
+            return Err(self.fail(ParseFailure { span, ..failure }, options));
+        }
+        Ok(())
+    }
+    // Recognizes whatever is still waiting, then checks the counts
+    fn finish(
+        &mut self,
+        options: &ParseOptions,
+        sink: &mut dyn FnMut(ParsedItem<F, A>) -> bool,
+    ) -> Result<(), ParseFailure<E>> {
+        self.recognize(true, options, sink)?;
+        let end = self.offset.saturating_sub(1);
+        self.recognizer
+            .finish((self.index, end..end), options)
+            .map_err(|failure| self.fail(failure, options))
+    }
+    // Finishes a failure the way `Arguments::parse_with` does
+    fn fail(&self, failure: ParseFailure<E>, options: &ParseOptions) -> ParseFailure<E> {
+        let failure = failure.with_candidates::<F>(options);
+        let failure = failure.with_messages(options.message_catalog());
+        if options.handles_errors() {
+            failure.error.handle_with(&failure.token);
+        }
+        failure
+    }
+}
+
+impl<F: ArgumentType, A: ArgumentType> Arguments<F, A> {
+    /// Parses like `Arguments::parse`, but lazily: each argument is only
+    /// recognized when the returned iterator reaches it, so a long list can
+    /// be handled without building an `Arguments` object, and an error
+    /// still says which argument failed.
+    ///
+    /// Pushing every item onto an empty `Arguments` gives the same result
+    /// as `Arguments::parse`. An argument is read from `env_args` only once
+    /// the items before it have been yielded, along with any that it has to
+    /// wait for (see `Parser`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use adante::{ArgumentType, Arguments, ParseError, ParsedItem};
+    ///
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum FlagType {
+    ///     Verbose,
+    /// }
+    /// impl ArgumentType for FlagType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "-v" | "--verbose" => Ok(Self::Verbose),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    /// }
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum ActionType {
+    ///     Add,
+    /// }
+    /// impl ArgumentType for ActionType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///         -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "a" | "add" => Ok(Self::Add),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let mut items =
+    ///     Arguments::<FlagType, ActionType>::parse_iter(vec!["add", "-q"], ParseError::UnknownFlag);
+    ///
+    /// assert!(matches!(items.next(), Some(Ok(ParsedItem::Action(ActionType::Add)))));
+    /// assert_eq!(items.next().unwrap().unwrap_err().index, 1);
+    /// assert!(items.next().is_none());
+    /// ```
+    pub fn parse_iter<'a, I, E>(env_args: I, error: E) -> ParseIter<I::IntoIter, F, A, E>
+    where
+        I: IntoIterator<Item = &'a str>,
+        E: Error + Clone,
+    {
+        Self::parse_iter_with(env_args, error, &ParseOptions::new())
+    }
+    /// Parses lazily like `Arguments::parse_iter`, with the given options.
+    /// Pushing every item onto an empty `Arguments` gives the same result
+    /// as `Arguments::parse_with`.
+    pub fn parse_iter_with<'a, I, E>(
+        env_args: I,
+        error: E,
+        options: &ParseOptions,
+    ) -> ParseIter<I::IntoIter, F, A, E>
+    where
+        I: IntoIterator<Item = &'a str>,
+        E: Error + Clone,
+    {
+        ParseIter {
+            tokens: env_args.into_iter(),
+            stream: Stream::new(error),
+            options: options.clone(),
+            ready: VecDeque::new(),
+            failure: None,
+            done: false,
+        }
+    }
+    /// Checks whether `env_args` would parse, without building an
+    /// `Arguments` object. Fails with the same error as `Arguments::parse`.
+    pub fn validate_args<S: AsRef<str>, E: Error + Clone>(env_args: &[S], error: E) -> Result<(), E> {
+        Self::validate_args_with(env_args, error, &ParseOptions::new())
+    }
+    /// Checks whether `env_args` would parse with the given options, failing
+    /// with the same error as `Arguments::parse_with`.
+    pub fn validate_args_with<S: AsRef<str>, E: Error + Clone>(
+        env_args: &[S],
+        error: E,
+        options: &ParseOptions,
+    ) -> Result<(), E> {
+        for item in Self::parse_iter_with(env_args.iter().map(AsRef::as_ref), error, options) {
+            item.map_err(|failure| failure.error)?;
+        }
+        Ok(())
+    }
+    /// Splits `env_args` into the arguments that parse on their own as a
+    /// flag or action and those that don't, keeping their order, for tools
+    /// that handle some arguments and forward the rest. No `Arguments`
+    /// object is built.
+    pub fn partition_args<S: AsRef<str>>(env_args: &[S]) -> (Vec<String>, Vec<String>) {
+        Self::partition_args_with(env_args, &ParseOptions::new())
+    }
+    /// Splits `env_args` like `Arguments::partition_args`, recognizing each
+    /// argument with the given options. Counts such as
+    /// `ParseOptions::action_policy` aren't checked, and errors aren't
+    /// handed to `Error::handle_with`.
+    pub fn partition_args_with<S: AsRef<str>>(
+        env_args: &[S],
+        options: &ParseOptions,
+    ) -> (Vec<String>, Vec<String>) {
+        let options = options.clone().handle_errors(false);
+        env_args.iter().map(|arg| arg.as_ref().to_string()).partition(|arg| {
+            let mut stream = Stream::<F, A, _>::new(ParseError::UnknownFlag);
+            let (mut found, mut known) = (0, true);
+            let mut sink = |item| {
+                found += 1;
+                known &= matches!(item, ParsedItem::Flag(_) | ParsedItem::Action(_));
+                true
+            };
+            let result = stream.push(arg, &options, &mut sink);
+            let result = result.and_then(|()| stream.recognize(true, &options, &mut sink));
+            result.is_ok() && found > 0 && known
+        })
+    }
+    /// Parses `env_args` for a tool that takes only flags, returning just
+    /// the flags. Any argument that isn't a flag, even a known action, fails
+    /// with `Error::unknown_action`.
+    pub fn flags_only<S: AsRef<str>, E: Error + Clone>(
+        env_args: &[S],
+        error: E,
+    ) -> Result<Vec<Flag<F>>, E> {
+        Self::flags_only_with(env_args, error, &ParseOptions::new())
+    }
+    /// Parses `env_args` like `Arguments::flags_only`, with the given
+    /// options.
+    pub fn flags_only_with<S: AsRef<str>, E: Error + Clone>(
+        env_args: &[S],
+        error: E,
+        options: &ParseOptions,
+    ) -> Result<Vec<Flag<F>>, E> {
+        let mut flags = Vec::new();
+        let env_args = env_args.iter().map(AsRef::as_ref);
+        for item in Self::parse_iter_with(env_args, error.clone(), options) {
+            match item.map_err(|failure| failure.error)? {
+                ParsedItem::Flag(flag) => flags.push(flag),
+                ParsedItem::Action(_) | ParsedItem::Positional(_) | ParsedItem::Passthrough(..) => {
+                    return Err(error.unknown_action())
+                }
+            }
+        }
+        Ok(flags)
+    }
+}
+
+impl<F, A> Arguments<F, A> {
+    // Adds an item to the field it belongs in
+    fn push_item(&mut self, item: ParsedItem<F, A>) {
+        match item {
+            ParsedItem::Flag(flag) => self.flags.push(flag),
+            ParsedItem::Action(action) => self.add_action(action),
+            ParsedItem::Positional(value) => self.positionals.push(value),
+            ParsedItem::Passthrough(key, value) => self.passthrough.push((key, value)),
+        }
+    }
+}
+
+/// Yields the flags and actions in the order they were passed, followed by
+/// the positional arguments and the `passthrough` pairs. Collecting the
+/// items back into an `Arguments` gives an equal object.
+impl<F, A> IntoIterator for Arguments<F, A> {
+    type Item = ParsedItem<F, A>;
+    type IntoIter = vec::IntoIter<ParsedItem<F, A>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let offsets: Vec<usize> = (0..self.actions.len()).map(|n| self.flags_before(n)).collect();
+        let mut items = Vec::with_capacity(self.flags.len() + self.actions.len());
+        let mut flags = self.flags.into_iter().enumerate().peekable();
+        for (action, offset) in self.actions.into_iter().zip(offsets) {
+            while let Some((_, flag)) = flags.next_if(|&(i, _)| i < offset) {
+                items.push(ParsedItem::Flag(flag));
+            }
+            items.push(ParsedItem::Action(action));
+        }
+        items.extend(flags.map(|(_, flag)| ParsedItem::Flag(flag)));
+        items.extend(self.positionals.into_iter().map(ParsedItem::Positional));
+        let pairs = self.passthrough.into_iter();
+        items.extend(pairs.map(|(key, value)| ParsedItem::Passthrough(key, value)));
+        items.into_iter()
+    }
+}
+
+/// Yields a reference to each item, in the same order as
+/// `Arguments::into_iter`.
+impl<'a, F, A> IntoIterator for &'a Arguments<F, A> {
+    type Item = ParsedRef<'a, F, A>;
+    type IntoIter = Items<'a, F, A>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Items {
+            args: self,
+            flag: 0,
+            action: 0,
+            positional: 0,
+            pair: 0,
+        }
+    }
+}
+
+/// A borrowed `ParsedItem`, as yielded by iterating over `&Arguments`.
+#[derive(Debug, PartialEq)]
+pub enum ParsedRef<'a, F, A> {
+    /// A flag, with its value if one was given
+    Flag(&'a Flag<F>),
+    /// An action
+    Action(&'a A),
+    /// An argument that is neither a flag nor an action
+    Positional(&'a str),
+    /// A `key=value` pair after `--`
+    Passthrough(&'a str, &'a str),
+}
+
+impl<F, A> Clone for ParsedRef<'_, F, A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<F, A> Copy for ParsedRef<'_, F, A> {}
+
+impl<F: Clone, A: Clone> ParsedRef<'_, F, A> {
+    /// Clones the item into an owned `ParsedItem`.
+    pub fn cloned(self) -> ParsedItem<F, A> {
+        match self {
+            ParsedRef::Flag(flag) => ParsedItem::Flag(flag.clone()),
+            ParsedRef::Action(action) => ParsedItem::Action(action.clone()),
+            ParsedRef::Positional(value) => ParsedItem::Positional(value.to_string()),
+            ParsedRef::Passthrough(key, value) => {
+                ParsedItem::Passthrough(key.to_string(), value.to_string())
+            }
+        }
+    }
+}
+
+/// The iterator over `&Arguments`, borrowing each item in place.
+pub struct Items<'a, F, A> {
+    args: &'a Arguments<F, A>,
+    flag: usize,
+    action: usize,
+    positional: usize,
+    pair: usize,
+}
+
+impl<'a, F, A> Iterator for Items<'a, F, A> {
+    type Item = ParsedRef<'a, F, A>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let args = self.args;
+        // Flags passed before the next action come first, then the action:
+        if self.action < args.actions.len() {
+            if self.flag < args.flags_before(self.action) {
+                self.flag += 1;
+                return Some(ParsedRef::Flag(&args.flags[self.flag - 1]));
+            }
+            self.action += 1;
+            return Some(ParsedRef::Action(&args.actions[self.action - 1]));
+        }
+        if let Some(flag) = args.flags.get(self.flag) {
+            self.flag += 1;
+            return Some(ParsedRef::Flag(flag));
+        }
+        if let Some(value) = args.positionals.get(self.positional) {
+            self.positional += 1;
+            return Some(ParsedRef::Positional(value));
+        }
+        let (key, value) = args.passthrough.get(self.pair)?;
+        self.pair += 1;
+        Some(ParsedRef::Passthrough(key, value))
+    }
+}
+
+impl<F, A> FromIterator<ParsedItem<F, A>> for Arguments<F, A> {
+    fn from_iter<I: IntoIterator<Item = ParsedItem<F, A>>>(items: I) -> Self {
+        let mut args = Arguments::default();
+        args.extend(items);
+        args
+    }
+}
+
+/// Pushes each item onto the field it belongs in, recording actions as
+/// `Arguments::add_action` does.
+///
+/// There is no `Extend<A>` for actions on their own: it would overlap with
+/// `Extend<Flag<F, V>>` whenever the action type is a `Flag`, which Rust
+/// doesn't allow. Push actions with `Arguments::add_action`, or wrap them
+/// with `args.extend(actions.map(ParsedItem::Action))`.
+impl<F, A> Extend<ParsedItem<F, A>> for Arguments<F, A> {
+    fn extend<I: IntoIterator<Item = ParsedItem<F, A>>>(&mut self, items: I) {
+        for item in items {
+            self.push_item(item);
+        }
+    }
+}
+
+impl<F, A, V> Extend<Flag<F, V>> for Arguments<F, A, V> {
+    fn extend<I: IntoIterator<Item = Flag<F, V>>>(&mut self, flags: I) {
+        self.flags.extend(flags);
+    }
+}
@@ -0,0 +1,358 @@
+use crate::token::{self, TokenKind};
+use crate::{attached_flag, is_known_cluster, lookup_flag, Deferred};
+use crate::{ActionPolicy, ArgumentType, Error, Flag, FlagStyle, KeyStyle, ParseFailure, ParseOptions, Warning};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::ops::Range;
+
+// An argument recognized by a `Recognizer`, borrowing its text from the
+// arguments it was read from
+pub(crate) enum Item<'t, F, A> {
+    Flag(Flag<F, &'t str>),
+    Action(A),
+    Positional(&'t str),
+    // A `key=value` pair after `--`, see `ParseOptions::passthrough_pairs`
+    Pair(&'t str, &'t str),
+}
+
+// Recognizes arguments the way `Arguments::parse_with` does, one step at a
+// time, so that the whole-list parse, `Parser` and `ParseIter` share it.
+// It only keeps what carries over from one argument to the next; the
+// action and positional counts are checked by `finish`.
+#[derive(Debug)]
+pub(crate) struct Recognizer<E> {
+    pub(crate) error: E,
+    operands_only: bool,
+    passthrough: bool,
+    actions: usize,
+    positionals: usize,
+    // The arguments the counts fail at, if they do
+    second_action: Option<Recorded>,
+    extra_positional: Option<Recorded>,
+}
+
+// An argument kept for a failure reported once every argument is read
+#[derive(Debug)]
+struct Recorded {
+    index: usize,
+    token: String,
+    span: Range<usize>,
+}
+
+impl<E: Error + Clone> Recognizer<E> {
+    pub(crate) fn new(error: E) -> Self {
+        Recognizer {
+            error,
+            operands_only: false,
+            passthrough: false,
+            actions: 0,
+            positionals: 0,
+            second_action: None,
+            extra_positional: None,
+        }
+    }
+    // Recognizes the first of `window`, the arguments not read yet, adding
+    // what it makes up to `items`. Returns how many arguments it used, or
+    // `None` if it needs more than the window holds and `end` says more may
+    // follow, in which case nothing is changed. `at` is the index and span
+    // reported if the argument ends up failing a count. On failure, the
+    // items added for the argument should be dropped.
+    pub(crate) fn step<'t, F: ArgumentType, A: ArgumentType>(
+        &mut self,
+        (window, end): (&[&'t str], bool),
+        at: (usize, &Range<usize>),
+        options: &ParseOptions,
+        items: &mut Vec<Item<'t, F, A>>,
+        warnings: &mut Vec<Warning>,
+    ) -> Result<Option<usize>, E> {
+        let error = &self.error;
+        let arg = window[0];
+        // Everything after `--` is a `key=value` pair if those are
+        // collected:
+        if self.passthrough {
+            let (key, value) = arg.split_once(options.separator()).ok_or_else(|| error.clone())?;
+            items.push(Item::Pair(key, value));
+            return Ok(Some(1));
+        }
+        let kind = token::classify(arg, options);
+        if kind == TokenKind::Terminator && !self.operands_only {
+            self.passthrough = options.collects_passthrough_pairs();
+            if self.passthrough {
+                return Ok(Some(1));
+            }
+        }
+        // Everything after `--` is positional, and in POSIX mode so is
+        // everything after the first action or positional:
+        let terminator = kind == TokenKind::Terminator;
+        if self.operands_only
+            || (terminator && (options.positional_policy().is_some() || options.is_posix()))
+        {
+            if self.operands_only {
+                self.push_positional(arg, at, options, items);
+            }
+            self.operands_only = true;
+            return Ok(Some(1));
+        }
+        if arg.is_empty() {
+            return Err(error.empty_arg());
+        }
+        let (key, value, style) = match kind {
+            TokenKind::ShortFlag { key, value } => (key, value, FlagStyle::Short),
+            TokenKind::LongFlag { key, value } => (key, value, FlagStyle::Long),
+            // Looked up like any other flag:
+            TokenKind::Terminator => (arg, None, FlagStyle::Long),
+            // A lone `-`, as for stdin, is an action rather than a flag:
+            TokenKind::Word | TokenKind::StdinDash => {
+                return self.step_word((window, end), at, options, items);
+            }
+        };
+        // TODO: Recognize file path, omit or save to output
+        let flag = match value {
+            // No value given:
+            None => match lookup_flag::<F, _>(key, None, Deferred, options) {
+                Ok(key) => Flag {
+                    key,
+                    value: None,
+                    values: Vec::new(),
+                    style,
+                },
+                Err(Deferred) => {
+                    // Take `-Dname` as the map flag `-D` with the value `name`,
+                    // and `-ofile` as `-o` with the value `file` if enabled:
+                    if let Some((key, val)) = attached_flag::<F, _>(arg, Deferred, options) {
+                        if key.is_map() && options.requires_map_pairs() && !val.contains('=') {
+                            return Err(error.clone());
+                        }
+                        let flag = Flag {
+                            key,
+                            value: Some(val),
+                            values: Vec::new(),
+                            style: FlagStyle::Short,
+                        };
+                        push_flag(flag, (at.0, arg), items, warnings);
+                        return Ok(Some(1));
+                    }
+                    // Retry `--no-color` as `--color` with the value `false`:
+                    let negated = options
+                        .negation()
+                        .and_then(|prefix| arg.strip_prefix("--")?.strip_prefix(prefix));
+                    if let Some(positive) = negated {
+                        let positive = format!("--{}", positive);
+                        let key = lookup_flag::<F, _>(&positive, None, Deferred, options)
+                            .map_err(|_| error.unknown_flag())?;
+                        let flag = Flag {
+                            key,
+                            value: Some("false"),
+                            values: Vec::new(),
+                            style: FlagStyle::Long,
+                        };
+                        push_flag(flag, (at.0, arg), items, warnings);
+                        return Ok(Some(1));
+                    }
+                    // Expand `-vh` into `-v -h` if every letter is a known flag:
+                    if !options.expands_short_clusters() || !is_known_cluster::<F>(arg) {
+                        return Err(error.unknown_flag());
+                    }
+                    for c in arg.chars().skip(1) {
+                        let short = format!("-{}", c);
+                        let key = lookup_flag::<F, _>(&short, None, Deferred, options);
+                        let flag = Flag {
+                            key: key.map_err(|_| error.unknown_flag())?,
+                            value: None,
+                            values: Vec::new(),
+                            style: FlagStyle::Short,
+                        };
+                        if options.requires_values() && flag.key.takes_value() {
+                            return Err(error.missing_value());
+                        }
+                        push_flag(flag, (at.0, arg), items, warnings);
+                    }
+                    return Ok(Some(1));
+                }
+            },
+            // Value given after the separator:
+            Some(val) => {
+                let (key, val) = match lookup_flag(key, Some(val), Deferred, options) {
+                    Ok(key) => (key, val),
+                    // Take `-Dname=value` as the map flag `-D` with the value `name=value`:
+                    Err(Deferred) => attached_flag::<F, _>(arg, Deferred, options)
+                        .ok_or_else(|| error.unknown_flag())?,
+                };
+                // Reject values given to flags that can't take one:
+                if !key.takes_value() {
+                    return Err(error.unexpected_value());
+                }
+                if key.is_map() && options.requires_map_pairs() && !val.contains('=') {
+                    return Err(error.clone());
+                }
+                let flag = Flag {
+                    key,
+                    value: Some(val),
+                    values: Vec::new(),
+                    style,
+                };
+                push_flag(flag, (at.0, arg), items, warnings);
+                return Ok(Some(1));
+            }
+        };
+        let mut flag = flag;
+        let mut used = 1;
+        // Consume the following non-flag arguments as values:
+        if options.collects_greedy_values() && flag.key.takes_value() {
+            loop {
+                match window.get(used) {
+                    Some(next) if next.is_empty() || next.starts_with('-') => break,
+                    Some(next) => flag.values.push(*next),
+                    None if end => break,
+                    None => return Ok(None),
+                }
+                used += 1;
+            }
+        }
+        if options.requires_values() && flag.key.takes_value() && flag.values.is_empty() {
+            return Err(error.missing_value());
+        }
+        push_flag(flag, (at.0, arg), items, warnings);
+        Ok(Some(used))
+    }
+    // Recognizes a word as an action, or as a positional if those are
+    // collected
+    fn step_word<'t, F, A: ArgumentType>(
+        &mut self,
+        (window, end): (&[&'t str], bool),
+        at: (usize, &Range<usize>),
+        options: &ParseOptions,
+        items: &mut Vec<Item<'t, F, A>>,
+    ) -> Result<Option<usize>, E> {
+        let arg = window[0];
+        // Assume action, match string to type
+        let action = if options.strips_prefix() {
+            A::from_key(arg, KeyStyle::Action, Deferred)
+        } else {
+            A::from_str(arg, Deferred)
+        };
+        // Retry with the next argument, for actions that carry data:
+        let (action, used) = match (action, window.get(1)) {
+            (Ok(action), _) => (Ok(action), 1),
+            (Err(Deferred), None) if !end && may_carry_data::<A>(arg) => return Ok(None),
+            (Err(Deferred), next) => match A::from_str_with_arg(arg, next.copied(), Deferred) {
+                Ok(action) => (Ok(action), 1 + next.is_some() as usize),
+                Err(Deferred) => (Err(self.error.unknown_action()), 1),
+            },
+        };
+        match (action, options.positional_policy()) {
+            (Ok(action), _) => {
+                items.push(Item::Action(action));
+                self.actions += 1;
+                if self.actions == 2 {
+                    self.second_action = Some(Recorded::new(at, arg));
+                }
+            }
+            // Keep anything that isn't an action as a positional:
+            (Err(_), Some(_)) => self.push_positional(arg, at, options, items),
+            (Err(e), None) => return Err(e),
+        }
+        self.operands_only = options.is_posix();
+        Ok(Some(used))
+    }
+    // Adds a positional, noting it if it's one too many
+    fn push_positional<'t, F, A>(
+        &mut self,
+        arg: &'t str,
+        at: (usize, &Range<usize>),
+        options: &ParseOptions,
+        items: &mut Vec<Item<'t, F, A>>,
+    ) {
+        items.push(Item::Positional(arg));
+        self.positionals += 1;
+        let max = options.positional_policy().and_then(|policy| policy.max);
+        if max == Some(self.positionals - 1) {
+            self.extra_positional = Some(Recorded::new(at, arg));
+        }
+    }
+    // Checks the action and positional counts once every argument has been
+    // recognized, given the index and span just past the last one
+    pub(crate) fn finish(
+        &self,
+        (index, span): (usize, Range<usize>),
+        options: &ParseOptions,
+    ) -> Result<(), ParseFailure<E>> {
+        let missing = |error: E| ParseFailure {
+            span: span.clone(),
+            ..ParseFailure::new(error, index, "")
+        };
+        // Enforce the action count:
+        match (options.actions(), &self.second_action) {
+            (ActionPolicy::AtMostOne, Some(second)) | (ActionPolicy::ExactlyOne, Some(second)) => {
+                return Err(second.fail(self.error.clone()));
+            }
+            (ActionPolicy::ExactlyOne, None) if self.actions == 0 => {
+                return Err(missing(self.error.clone()));
+            }
+            _ => (),
+        }
+        // Enforce the positional count:
+        if let Some(policy) = options.positional_policy() {
+            let found = self.positionals;
+            if let Some(extra) = &self.extra_positional {
+                return Err(extra.fail(self.error.positional_count(found)));
+            }
+            if found < policy.min {
+                return Err(missing(self.error.positional_count(found)));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Recorded {
+    fn new((index, span): (usize, &Range<usize>), token: &str) -> Self {
+        Recorded {
+            index,
+            token: token.to_string(),
+            span: span.clone(),
+        }
+    }
+    fn fail<E>(&self, error: E) -> ParseFailure<E> {
+        ParseFailure {
+            span: self.span.clone(),
+            ..ParseFailure::new(error, self.index, &self.token)
+        }
+    }
+}
+
+// Adds a flag, warning if it's deprecated
+fn push_flag<'t, F: ArgumentType, A>(
+    flag: Flag<F, &'t str>,
+    (index, token): (usize, &str),
+    items: &mut Vec<Item<'t, F, A>>,
+    warnings: &mut Vec<Warning>,
+) {
+    if let Some(message) = flag.key.deprecated() {
+        warnings.push(Warning {
+            token: token.to_string(),
+            index,
+            message,
+        });
+    }
+    items.push(Item::Flag(flag));
+}
+
+// Returns whether a word that `from_str` rejects may still be an action
+// that takes the argument after it, so it's worth waiting for that
+// argument. Types that list their keys only wait for those keys.
+fn may_carry_data<A: ArgumentType>(arg: &str) -> bool {
+    let keys = A::known_keys();
+    keys.is_empty() || keys.contains(&arg)
+}
+
+// Converts a flag's borrowed values into the parse's value type
+pub(crate) fn convert_flag<'t, F, V: From<&'t str>>(flag: Flag<F, &'t str>) -> Flag<F, V> {
+    Flag {
+        key: flag.key,
+        value: flag.value.map(V::from),
+        values: flag.values.into_iter().map(V::from).collect(),
+        style: flag.style,
+    }
+}
@@ -0,0 +1,105 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// An error returned by `split` when a line can't be broken into words.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SplitError {
+    /// A quote was opened but never closed; holds the quote character.
+    UnterminatedQuote(char),
+    /// The line ended with a backslash that has nothing to escape.
+    TrailingBackslash,
+}
+
+impl fmt::Display for SplitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnterminatedQuote(quote) => write!(f, "unterminated {} quote", quote),
+            Self::TrailingBackslash => write!(f, "trailing backslash"),
+        }
+    }
+}
+
+/// Splits a command line into words the way a POSIX shell would, without
+/// any expansion.
+///
+/// - Words are separated by whitespace.
+/// - Single quotes keep everything up to the next single quote literally.
+/// - Double quotes keep everything up to the next double quote, except that
+///   a backslash escapes a following `"`, `\`, `$` or `` ` ``.
+/// - Outside of quotes, a backslash escapes any following character.
+///
+/// Quotes can appear in the middle of a word, so `--name='O Brien'` is the
+/// single word `--name=O Brien`.
+///
+/// # Examples
+/// ```
+/// use adante::{split, SplitError};
+///
+/// assert_eq!(split(r#"-m "hello world" add"#).unwrap(), vec!["-m", "hello world", "add"]);
+/// assert_eq!(split("--name='O Brien'").unwrap(), vec!["--name=O Brien"]);
+/// assert_eq!(split("-m 'oops"), Err(SplitError::UnterminatedQuote('\'')));
+/// ```
+pub fn split(line: &str) -> Result<Vec<String>, SplitError> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    // A word can be empty but still present, as in `""`:
+    let mut in_word = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(core::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(SplitError::UnterminatedQuote('\'')),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ '"') | Some(c @ '\\') | Some(c @ '$') | Some(c @ '`') => {
+                                word.push(c)
+                            }
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(SplitError::UnterminatedQuote('"')),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(SplitError::UnterminatedQuote('"')),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                match chars.next() {
+                    Some(c) => word.push(c),
+                    None => return Err(SplitError::TrailingBackslash),
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
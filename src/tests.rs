@@ -1,4 +1,20 @@
-use crate::{ArgumentType, Arguments, Error};
+// The crate may be built without std, but the tests always have it
+extern crate std;
+use std::prelude::v1::*;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::{println, vec};
+
+use crate::check;
+use crate::completion::{complete, Completion};
+use crate::common::CommonFlags;
+use crate::token::{classify, TokenKind};
+use crate::{
+    split, ActionPolicy, ArgumentType, Arguments, DuplicatePolicy, Error, Flag, FlagMatch,
+    FlagStyle, KeyStyle, Messages, ParseError, ParseFailure, ParseKey, ParseOptions, ParsedItem,
+    ParsedRef, Parser, PositionalPolicy, Rule, SingleArguments, SplitError,
+};
 
 #[derive(Debug, Clone, Copy)]
 pub enum TestErrorType {
@@ -20,13 +36,22 @@ impl Error for TestErrorType {
             Self::NotRecognized => "Action or flag is not recognized",
         }
     }
+    fn unexpected_value(&self) -> Self {
+        Self::FlagVal
+    }
+    fn missing_value(&self) -> Self {
+        Self::NoFlagVal
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 enum TestFlagType {
     Help,
     Verbose,
     Print,
+    Level,
+    Files,
     TestFail, // NOTE: For testing only, use instead of std::process::exit
 }
 impl ArgumentType for TestFlagType {
@@ -36,12 +61,43 @@ impl ArgumentType for TestFlagType {
             "-h" | "--help" => Ok(Self::Help),
             "-v" | "--verbose" => Ok(Self::Verbose),
             "-p" | "--print" => Ok(Self::Print),
+            "-l" | "--level" => Ok(Self::Level),
+            "-f" | "--files" => Ok(Self::Files),
             _ => Err(error),
         }
     }
+    // Levels only go from 0 to 3
+    fn from_str_with_value<E: Error>(key: &str, value: Option<&str>, error: E)
+                                     -> Result<Self, E> {
+        match (key, value.map(|v| v.parse::<u8>())) {
+            ("-l" | "--level", Some(Ok(level))) if level > 3 => Err(error),
+            ("-l" | "--level", Some(Err(_))) => Err(error),
+            _ => Self::from_str(key, error),
+        }
+    }
+    fn known_keys() -> &'static [&'static str] {
+        &["-h", "--help", "-v", "--verbose", "-p", "--print", "-l", "--level", "-f", "--files"]
+    }
+    fn variants() -> &'static [Self] {
+        &[Self::Help, Self::Verbose, Self::Print, Self::Level, Self::Files, Self::TestFail]
+    }
+    fn canonical_key(&self) -> &'static str {
+        match self {
+            Self::Help => "--help",
+            Self::Verbose => "--verbose",
+            Self::Print => "--print",
+            Self::Level => "--level",
+            Self::Files => "--files",
+            Self::TestFail => "",
+        }
+    }
+    fn takes_value(&self) -> bool {
+        !matches!(self, Self::Verbose)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 enum TestActionType {
     Add,
     Remove,
@@ -58,10 +114,39 @@ impl ArgumentType for TestActionType {
             _ => Err(error),
         }
     }
+    fn known_keys() -> &'static [&'static str] {
+        &["add", "a", "remove", "r", "edit", "e"]
+    }
+    fn variants() -> &'static [Self] {
+        &[Self::Add, Self::Remove, Self::Edit, Self::TestFail]
+    }
+    fn canonical_key(&self) -> &'static str {
+        match self {
+            Self::Add => "add",
+            Self::Remove => "remove",
+            Self::Edit => "edit",
+            Self::TestFail => "",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TestSubFlagType {
+    Name,
+}
+
+impl ArgumentType for TestSubFlagType {
+    fn from_str<TestErrorType>(key: &str, error: TestErrorType) -> Result<Self, TestErrorType> {
+        match key {
+            "-n" | "--name" => Ok(Self::Name),
+            _ => Err(error),
+        }
+    }
 }
 
 // "Simulates" running a program with arguments, collected by std::env::args::collect()
 // NOTE: File path is omitted, would cause error as of 01-11
+#[allow(clippy::diverging_sub_expression)]
 fn simulate(env_args: Vec<&str>) -> Result<Arguments<TestFlagType, TestActionType>, TestErrorType> {
     let _env_args: Arguments<TestFlagType, TestActionType> =
         return match Arguments::parse(env_args, TestErrorType::Syntax) {
@@ -70,6 +155,14 @@ fn simulate(env_args: Vec<&str>) -> Result<Arguments<TestFlagType, TestActionTyp
         };
 }
 
+// Same as simulate, but with parse options applied
+fn simulate_with(
+    env_args: Vec<&str>,
+    options: &ParseOptions,
+) -> Result<Arguments<TestFlagType, TestActionType>, ParseFailure<TestErrorType>> {
+    Arguments::parse_with_dedup(env_args, TestErrorType::Syntax, options)
+}
+
 #[test]
 fn parse_flag_key_from_str() {
     let env_args = match simulate(vec!["-v"]) {
@@ -115,6 +208,12 @@ fn parse_val_flag() {
     assert_eq!(env_args.actions.len(), 0);
 }
 
+#[test]
+fn reject_val_for_valueless_flag() {
+    let result = simulate(vec!["-v=oops"]);
+    assert!(matches!(result, Err(TestErrorType::FlagVal)));
+}
+
 #[test]
 fn flag_from_str() {
     let ref_string = "-v";
@@ -160,3 +259,2375 @@ fn parse_action() {
     assert_eq!(env_args.actions.len(), 1);
     assert_eq!(env_args.flags.len(), 0);
 }
+
+#[test]
+fn retain_valued_flags() {
+    let mut env_args = simulate(vec!["-v", "-h=test", "-p"]).unwrap_or_default();
+    env_args.retain_flags(|flag| flag.value.is_some());
+    assert_eq!(env_args.flags.len(), 1);
+    assert_eq!(env_args.flags[0].key, TestFlagType::Help);
+}
+
+#[test]
+fn duplicate_flags_allowed_by_default() {
+    let env_args = simulate_with(vec!["-h=a", "-v", "-h=b"], &ParseOptions::new()).unwrap_or_default();
+    assert_eq!(env_args.flags.len(), 3);
+}
+
+#[test]
+fn duplicate_flags_error() {
+    let options = ParseOptions::new().duplicate_flags(DuplicatePolicy::Error);
+    let failure = simulate_with(vec!["-h=a", "-v", "-h=b"], &options).unwrap_err();
+    assert!(matches!(failure.error, TestErrorType::Syntax));
+    assert_eq!(failure.index, 2);
+    assert_eq!(failure.token, "-h=b");
+}
+
+#[test]
+fn duplicate_flags_first_wins() {
+    let options = ParseOptions::new().duplicate_flags(DuplicatePolicy::FirstWins);
+    let env_args = simulate_with(vec!["-h=a", "-v", "-h=b"], &options).unwrap_or_default();
+    assert_eq!(env_args.flags.len(), 2);
+    assert_eq!(env_args.flags[0].key, TestFlagType::Help);
+    assert_eq!(env_args.flags[0].value, Some("a".to_string()));
+    assert_eq!(env_args.flags[1].key, TestFlagType::Verbose);
+}
+
+#[test]
+fn duplicate_flags_last_wins() {
+    let options = ParseOptions::new().duplicate_flags(DuplicatePolicy::LastWins);
+    let env_args = simulate_with(vec!["-h=a", "-v", "-h=b"], &options).unwrap_or_default();
+    assert_eq!(env_args.flags.len(), 2);
+    assert_eq!(env_args.flags[0].key, TestFlagType::Verbose);
+    assert_eq!(env_args.flags[1].key, TestFlagType::Help);
+    assert_eq!(env_args.flags[1].value, Some("b".to_string()));
+}
+
+#[test]
+fn action_policy_counts() {
+    let inputs = [vec!["-v"], vec!["add", "-v"], vec!["add", "-v", "remove"]];
+    let cases = [
+        (ActionPolicy::Any, [true, true, true]),
+        (ActionPolicy::AtMostOne, [true, true, false]),
+        (ActionPolicy::ExactlyOne, [false, true, false]),
+    ];
+    for (policy, expected) in cases.iter() {
+        let options = ParseOptions::new().action_policy(*policy);
+        for (input, ok) in inputs.iter().zip(expected.iter()) {
+            let result = simulate_with(input.clone(), &options);
+            assert_eq!(result.is_ok(), *ok, "{:?} with {:?}", policy, input);
+        }
+    }
+}
+
+#[test]
+fn action_policy_names_second_action() {
+    let options = ParseOptions::new().action_policy(ActionPolicy::AtMostOne);
+    let failure = simulate_with(vec!["add", "-v", "remove"], &options).unwrap_err();
+    assert_eq!(failure.index, 2);
+    assert_eq!(failure.token, "remove");
+}
+
+#[test]
+fn action_policy_missing_action() {
+    let options = ParseOptions::new().action_policy(ActionPolicy::ExactlyOne);
+    let failure = simulate_with(vec!["-v"], &options).unwrap_err();
+    assert_eq!(failure.index, 1);
+    assert_eq!(failure.token, "");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn fill_missing_flag_from_env() {
+    std::env::set_var("ADANTE_TEST_PRINT", "yes");
+    std::env::set_var("ADANTE_TEST_VERBOSE", "no");
+    let mut env_args = simulate(vec!["-v"]).unwrap_or_default();
+    env_args.fill_from_env(&[
+        (TestFlagType::Print, "ADANTE_TEST_PRINT"),
+        (TestFlagType::Verbose, "ADANTE_TEST_VERBOSE"),
+        (TestFlagType::Help, "ADANTE_TEST_UNSET"),
+    ]);
+    assert_eq!(env_args.flags.len(), 2);
+    assert_eq!(env_args.flags[0].key, TestFlagType::Verbose);
+    assert_eq!(env_args.flags[0].value, None);
+    assert_eq!(env_args.flags[1].key, TestFlagType::Print);
+    assert_eq!(env_args.flags[1].value, Some("yes".to_string()));
+}
+
+#[test]
+fn reject_out_of_range_value() {
+    let env_args = simulate(vec!["--level=2"]).unwrap_or_default();
+    assert_eq!(env_args.flags[0].key, TestFlagType::Level);
+    assert!(simulate(vec!["--level=7"]).is_err());
+    assert!(simulate(vec!["--level=high"]).is_err());
+}
+
+#[test]
+fn parse_subcommand_flags() {
+    let result = Arguments::<TestFlagType, TestActionType>::parse_subcommands(
+        vec!["-v", "add", "--name=origin"],
+        TestErrorType::Syntax,
+        |action, rest| {
+            assert_eq!(*action, TestActionType::Add);
+            Arguments::<TestSubFlagType, TestActionType>::parse(rest.to_vec(), TestErrorType::Syntax)
+        },
+    );
+    let parsed = result.unwrap();
+    assert_eq!(parsed.global.len(), 1);
+    assert_eq!(parsed.global[0].key, TestFlagType::Verbose);
+    assert_eq!(parsed.action, Some(TestActionType::Add));
+    let sub = parsed.sub.unwrap();
+    assert_eq!(sub.flags.len(), 1);
+    assert_eq!(sub.flags[0].key, TestSubFlagType::Name);
+    assert_eq!(sub.flags[0].value, Some("origin".to_string()));
+}
+
+#[test]
+fn parse_subcommand_without_action() {
+    let result = Arguments::<TestFlagType, TestActionType>::parse_subcommands(
+        vec!["-v"],
+        TestErrorType::Syntax,
+        |_, rest| Arguments::<TestSubFlagType, TestActionType>::parse(rest.to_vec(), TestErrorType::Syntax),
+    );
+    let parsed = result.unwrap();
+    assert_eq!(parsed.global.len(), 1);
+    assert_eq!(parsed.action, None);
+    assert!(parsed.sub.is_none());
+}
+
+#[test]
+fn parse_subcommand_after_terminator() {
+    let result = Arguments::<TestFlagType, TestActionType>::parse_subcommands(
+        vec!["-v", "--", "add", "--name=origin"],
+        TestErrorType::Syntax,
+        |_, rest| Arguments::<TestSubFlagType, TestActionType>::parse(rest.to_vec(), TestErrorType::Syntax),
+    );
+    let parsed = result.unwrap();
+    assert_eq!(parsed.global.len(), 1);
+    assert_eq!(parsed.action, Some(TestActionType::Add));
+    assert_eq!(parsed.sub.unwrap().flags[0].key, TestSubFlagType::Name);
+
+    let result = Arguments::<TestFlagType, TestActionType>::parse_subcommands(
+        vec!["-v", "--"],
+        TestErrorType::Syntax,
+        |_, rest| Arguments::<TestSubFlagType, TestActionType>::parse(rest.to_vec(), TestErrorType::Syntax),
+    );
+    assert_eq!(result.unwrap().action, None);
+}
+
+type TestArguments = Arguments<TestFlagType, TestActionType>;
+
+#[test]
+fn split_at_action_first() {
+    let args = ["add", "-v", "--name=origin"];
+    let (index, before, after) = TestArguments::split_at_first_action(&args).unwrap();
+    assert_eq!(index, 0);
+    assert!(before.is_empty());
+    assert_eq!(after, ["-v", "--name=origin"]);
+}
+
+#[test]
+fn split_at_action_middle() {
+    let args = ["-v", "-h=x", "add", "--name=origin"];
+    let (index, before, after) = TestArguments::split_at_first_action(&args).unwrap();
+    assert_eq!(index, 2);
+    assert_eq!(before, ["-v", "-h=x"]);
+    assert_eq!(after, ["--name=origin"]);
+}
+
+#[test]
+fn split_without_action() {
+    let args = ["-v", "-h=x"];
+    assert!(TestArguments::split_at_first_action(&args).is_none());
+    assert!(TestArguments::split_at_first_action(&["-v", "--"]).is_none());
+}
+
+#[test]
+fn split_after_terminator() {
+    let args = ["-v", "--", "-add", "-p"];
+    let (index, before, after) = TestArguments::split_at_first_action(&args).unwrap();
+    assert_eq!(index, 2);
+    assert_eq!(before, ["-v"]);
+    assert_eq!(after, ["-p"]);
+}
+
+#[test]
+fn contains_present_action() {
+    let env_args = simulate(vec!["add", "-v", "edit"]).unwrap_or_default();
+    assert!(env_args.contains_action(TestActionType::Add));
+    assert!(env_args.contains_action(TestActionType::Edit));
+    assert_eq!(env_args.action_count(), 2);
+}
+
+#[test]
+fn contains_absent_action() {
+    let env_args = simulate(vec!["add", "-v"]).unwrap_or_default();
+    assert!(!env_args.contains_action(TestActionType::Remove));
+    assert_eq!(env_args.action_count(), 1);
+}
+
+#[test]
+fn strip_double_quotes() {
+    let options = ParseOptions::new().strip_quotes(true);
+    let env_args = simulate_with(vec!["-h=\"bob\""], &options).unwrap_or_default();
+    assert_eq!(env_args.flags[0].value, Some("bob".to_string()));
+}
+
+#[test]
+fn strip_single_quotes() {
+    let options = ParseOptions::new().strip_quotes(true);
+    let env_args = simulate_with(vec!["-h='O Brien'"], &options).unwrap_or_default();
+    assert_eq!(env_args.flags[0].value, Some("O Brien".to_string()));
+}
+
+#[test]
+fn strip_only_balanced_quotes() {
+    let options = ParseOptions::new().strip_quotes(true);
+    let env_args = simulate_with(vec!["-h=bob", "-p=\"bob'", "--print=\"'bob'\""], &options)
+        .unwrap_or_default();
+    assert_eq!(env_args.flags[0].value, Some("bob".to_string()));
+    assert_eq!(env_args.flags[1].value, Some("\"bob'".to_string()));
+    assert_eq!(env_args.flags[2].value, Some("'bob'".to_string()));
+}
+
+#[test]
+fn keep_quotes_by_default() {
+    let env_args = simulate(vec!["-h=\"bob\""]).unwrap_or_default();
+    assert_eq!(env_args.flags[0].value, Some("\"bob\"".to_string()));
+}
+
+#[test]
+fn parse_config_str() {
+    let contents = "# defaults\n\n  -v\n--level = 2\n   # indented comment\nadd\n";
+    let config = TestArguments::from_config_str(contents, TestErrorType::Syntax).unwrap();
+    assert_eq!(config.flags.len(), 2);
+    assert_eq!(config.flags[0].key, TestFlagType::Verbose);
+    assert_eq!(config.flags[1].key, TestFlagType::Level);
+    assert_eq!(config.flags[1].value, Some("2".to_string()));
+    assert_eq!(config.actions, vec![TestActionType::Add]);
+}
+
+#[test]
+fn reject_malformed_config_line() {
+    let contents = "-v\n\n# comment\n--colour=red\nadd\n";
+    let failure = TestArguments::from_config_str(contents, TestErrorType::Syntax).unwrap_err();
+    assert_eq!((failure.line, failure.index), (Some(4), 1));
+    assert_eq!(failure.token, "--colour=red");
+    assert_eq!(failure.to_string(), "Improper syntax usage (line 4: '--colour=red')");
+
+    let contents = "-v\n= 3\n";
+    let failure = TestArguments::from_config_str(contents, TestErrorType::Syntax).unwrap_err();
+    assert_eq!((failure.line, failure.index), (Some(2), 1));
+    assert_eq!(failure.token, "= 3");
+}
+
+#[test]
+fn summarize_arguments() {
+    let env_args = simulate(vec!["-h", "-p=x", "add"]).unwrap_or_default();
+    assert_eq!(env_args.summary(), "flags=[Help, Print=x] actions=[Add]");
+    assert_eq!(TestArguments::new().summary(), "flags=[] actions=[]");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json_round_trip() {
+    let env_args = simulate(vec!["-v", "-h=a \"quoted\" value", "add"]).unwrap_or_default();
+    let json = serde_json::to_string(&env_args).unwrap();
+    let restored: TestArguments = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored, env_args);
+
+    let env_args = simulate(vec!["-v", "add", "-p=x"]).unwrap_or_default();
+    let json = serde_json::to_string(&env_args).unwrap();
+    let restored: TestArguments = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.global_flags(), env_args.global_flags());
+    assert_eq!(restored.flags_after_action(0), env_args.flags_after_action(0));
+    assert_eq!(restored.global_flags().len(), 1);
+    // Data written before the offsets were serialized still reads
+    let restored: TestArguments = serde_json::from_str(r#"{"flags":[],"actions":[]}"#).unwrap();
+    assert_eq!(restored, TestArguments::new());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json_unordered_action_offsets() {
+    let flag = r#"{"key":"Verbose","value":null,"values":[],"style":"Short"}"#;
+    let json = format!(
+        r#"{{"flags":[{},{}],"actions":["Add","Remove"],"action_offsets":[2,0]}}"#,
+        flag, flag
+    );
+    let restored: TestArguments = serde_json::from_str(&json).unwrap();
+    assert!(restored.flags_after_action(0).is_empty());
+    assert_eq!(restored.flags_after_action(1).len(), 2);
+    assert_eq!(restored.global_flags().len(), 2);
+    assert_eq!((&restored).into_iter().count(), 4);
+    assert_eq!(restored.into_iter().count(), 4);
+}
+
+#[test]
+fn json_empty_arguments() {
+    assert_eq!(TestArguments::new().to_json(), r#"{"flags":[],"actions":[]}"#);
+}
+
+#[test]
+fn json_escapes_values() {
+    let env_args = simulate(vec!["-v", "-h=say \"hi\"\n\\ \t\u{1}", "add"]).unwrap_or_default();
+    assert_eq!(
+        env_args.to_json(),
+        r#"{"flags":[{"key":"Verbose","value":null},{"key":"Help","value":"say \"hi\"\n\\ \t\u0001"}],"actions":["Add"]}"#
+    );
+}
+
+#[test]
+fn json_non_ascii_values() {
+    let env_args = simulate(vec!["-h=héllo wörld ✓"]).unwrap_or_default();
+    assert_eq!(env_args.to_json(), r#"{"flags":[{"key":"Help","value":"héllo wörld ✓"}],"actions":[]}"#);
+}
+
+#[test]
+fn all_values_of_repeated_flag() {
+    let env_args = simulate(vec!["-p=A", "-v", "--print=B", "-p", "-p=C"]).unwrap_or_default();
+    assert_eq!(env_args.all_values_of(TestFlagType::Print), vec!["A", "B", "C"]);
+    assert!(env_args.all_values_of(TestFlagType::Help).is_empty());
+}
+
+#[test]
+fn expand_known_short_cluster() {
+    let options = ParseOptions::new().short_clusters(true);
+    let env_args = simulate_with(vec!["-vh", "add"], &options).unwrap_or_default();
+    assert_eq!(env_args.flags.len(), 2);
+    assert_eq!(env_args.flags[0].key, TestFlagType::Verbose);
+    assert_eq!(env_args.flags[1].key, TestFlagType::Help);
+    assert_eq!(env_args.actions, vec![TestActionType::Add]);
+}
+
+#[test]
+fn reject_unknown_short_cluster() {
+    let options = ParseOptions::new().short_clusters(true);
+    let failure = simulate_with(vec!["-v", "-xyz"], &options).unwrap_err();
+    assert_eq!(failure.index, 1);
+    assert_eq!(failure.token, "-xyz");
+    // Partly known clusters aren't expanded either
+    assert!(simulate_with(vec!["-vx"], &options).is_err());
+}
+
+#[test]
+fn no_short_clusters_by_default() {
+    assert!(simulate(vec!["-vh"]).is_err());
+}
+
+// Writes a file to the temp directory, named uniquely for this test run
+#[cfg(feature = "std")]
+fn temp_file(name: &str, contents: &str) -> String {
+    let path = std::env::temp_dir().join(format!("adante-{}-{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn expand_response_file() {
+    let path = temp_file("args.txt", "-v\n-p=x  add\n");
+    let at_path = format!("@{}", path);
+    let options = ParseOptions::new().expand_response_files(true);
+    let env_args = simulate_with(vec!["-h", &at_path, "edit"], &options).unwrap_or_default();
+    assert_eq!(env_args.summary(), "flags=[Help, Verbose, Print=x] actions=[Add, Edit]");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn expand_nested_response_files() {
+    let inner = temp_file("inner.txt", "-p=inner remove");
+    let outer = temp_file("outer.txt", &format!("-v @{} add", inner));
+    let at_outer = format!("@{}", outer);
+    let options = ParseOptions::new().expand_response_files(true);
+    let env_args = simulate_with(vec![&at_outer], &options).unwrap_or_default();
+    assert_eq!(env_args.summary(), "flags=[Verbose, Print=inner] actions=[Remove, Add]");
+
+    // Errors point at the argument the bad token was read from
+    let bad = temp_file("bad.txt", "-v --colour");
+    let at_bad = format!("@{}", bad);
+    let failure = simulate_with(vec!["-h", &at_bad], &options).unwrap_err();
+    assert_eq!(failure.index, 1);
+    assert_eq!(failure.token, "--colour");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn reject_recursive_response_file() {
+    let path = std::env::temp_dir().join(format!("adante-{}-loop.txt", std::process::id()));
+    let at_path = format!("@{}", path.to_str().unwrap());
+    std::fs::write(&path, &at_path).unwrap();
+    let options = ParseOptions::new().expand_response_files(true);
+    let failure = simulate_with(vec!["-v", &at_path], &options).unwrap_err();
+    assert_eq!(failure.index, 1);
+    assert_eq!(failure.token, at_path);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn reject_missing_response_file() {
+    let options = ParseOptions::new().expand_response_files(true);
+    let failure = simulate_with(vec!["-v", "@/nonexistent/adante-args.txt"], &options).unwrap_err();
+    assert!(matches!(failure.error, TestErrorType::Syntax));
+    assert_eq!(failure.index, 1);
+    assert_eq!(failure.token, "@/nonexistent/adante-args.txt");
+}
+
+#[test]
+fn split_double_quoted_word() {
+    assert_eq!(split(r#"-m "hello world""#).unwrap(), vec!["-m", "hello world"]);
+}
+
+#[test]
+fn split_single_quoted_word() {
+    assert_eq!(split("  --name='O Brien'  add ").unwrap(), vec!["--name=O Brien", "add"]);
+}
+
+#[test]
+fn split_escaped_quotes() {
+    assert_eq!(split(r#"-m "say \"hi\"" it\'s"#).unwrap(), vec!["-m", r#"say "hi""#, "it's"]);
+    assert_eq!(split(r#"'a\b' "c\d" "" e"#).unwrap(), vec![r"a\b", r"c\d", "", "e"]);
+}
+
+#[test]
+fn split_unterminated_quote() {
+    assert_eq!(split(r#"-m "hello"#), Err(SplitError::UnterminatedQuote('"')));
+    assert_eq!(split("-m 'hello"), Err(SplitError::UnterminatedQuote('\'')));
+    assert_eq!(split(r"-m hello\"), Err(SplitError::TrailingBackslash));
+}
+
+#[test]
+fn parse_quoted_line() {
+    let env_args = TestArguments::parse_line(r#"add -h="hello world" -v"#, TestErrorType::Syntax)
+        .unwrap_or_default();
+    assert_eq!(env_args.flags[0].value, Some("hello world".to_string()));
+    assert_eq!(env_args.flags[1].key, TestFlagType::Verbose);
+    assert_eq!(env_args.actions, vec![TestActionType::Add]);
+    assert!(TestArguments::parse_line("add -h='oops", TestErrorType::Syntax).is_err());
+}
+
+#[test]
+fn parse_str_with_quotes() {
+    let env_args =
+        TestArguments::parse_str(r#"add -v --print='hi there' "edit""#, TestErrorType::Syntax).unwrap();
+    assert_eq!(env_args.flags[0].key, TestFlagType::Verbose);
+    assert_eq!(env_args.flags[1].value, Some("hi there".to_string()));
+    assert_eq!(env_args.actions, vec![TestActionType::Add, TestActionType::Edit]);
+    assert!(matches!(
+        TestArguments::parse_str("add --print=\"oops", TestErrorType::NotRecognized),
+        Err(TestErrorType::NotRecognized)
+    ));
+}
+
+#[test]
+fn destructure_into_parts() {
+    let env_args = simulate(vec!["-v", "add", "-h=x", "edit"]).unwrap_or_default();
+    let (flags, actions) = env_args.into_parts();
+    assert_eq!(flags.len(), 2);
+    assert_eq!(actions.len(), 2);
+    assert_eq!(flags[1].value, Some("x".to_string()));
+}
+
+#[test]
+fn parse_with_builtin_error() {
+    let parse = |env_args| TestArguments::parse(env_args, ParseError::EmptyArg);
+    assert!(parse(vec!["-v", "-h=x", "add"]).is_ok());
+    assert_eq!(parse(vec!["add", "--colour"]).unwrap_err(), ParseError::UnknownFlag);
+    assert_eq!(parse(vec!["-v", "frobnicate"]).unwrap_err(), ParseError::UnknownAction);
+    assert_eq!(parse(vec!["-v=x"]).unwrap_err(), ParseError::UnexpectedValue);
+    assert_eq!(parse(vec!["-v", ""]).unwrap_err(), ParseError::EmptyArg);
+}
+
+// An error carrying data, so it can't be Copy
+#[derive(Debug, Clone, PartialEq)]
+struct TestMessageError(String);
+
+impl Error for TestMessageError {
+    fn handle(&self) {
+        println!("{}", self.0);
+    }
+    fn as_str(&self) -> &str {
+        &self.0
+    }
+    fn unknown_action(&self) -> Self {
+        TestMessageError(format!("{}: unknown action", self.0))
+    }
+    fn positional_count(&self, found: usize) -> Self {
+        TestMessageError(format!("{}: {} positionals", self.0, found))
+    }
+}
+
+#[test]
+fn parse_with_non_copy_error() {
+    let error = TestMessageError("parse".to_string());
+    let env_args = TestArguments::parse(vec!["-v", "add"], error.clone()).unwrap();
+    assert_eq!(env_args.actions, vec![TestActionType::Add]);
+    let failure = TestArguments::parse(vec!["-v", "frobnicate"], error).unwrap_err();
+    assert_eq!(failure.as_str(), "parse: unknown action");
+}
+
+// An error that counts how many times it's cloned
+#[derive(Debug)]
+struct TestCountedError(Rc<Cell<usize>>);
+
+impl Clone for TestCountedError {
+    fn clone(&self) -> Self {
+        self.0.set(self.0.get() + 1);
+        TestCountedError(Rc::clone(&self.0))
+    }
+}
+
+impl Error for TestCountedError {
+    fn handle(&self) {}
+    fn as_str(&self) -> &str {
+        "counted"
+    }
+}
+
+#[test]
+fn parse_clones_error_only_on_failure() {
+    let clones = Rc::new(Cell::new(0));
+    let line = vec!["-v", "add", "--level=2", "-p=x", "-vh", "--no-help", "/f:a"];
+    let options = ParseOptions::new().short_clusters(true).negation_prefix("no-").slash_flags(true);
+    let error = TestCountedError(Rc::clone(&clones));
+    assert!(TestArguments::parse_with(line, error, &options).is_ok());
+    assert_eq!(clones.get(), 0);
+    let error = TestCountedError(Rc::clone(&clones));
+    assert!(TestArguments::parse(vec!["-v", "edit", "r"], error).is_ok());
+    assert_eq!(clones.get(), 0);
+    let error = TestCountedError(Rc::clone(&clones));
+    assert!(TestArguments::parse(vec!["-v", "--frob"], error).is_err());
+    assert_eq!(clones.get(), 1);
+}
+
+#[test]
+fn parse_borrowed_values() {
+    let source = [String::from("-h=borrowed"), String::from("add"), String::from("-p='x'")];
+    let env_args: Vec<&str> = source.iter().map(String::as_str).collect();
+    {
+        let parsed: Arguments<TestFlagType, TestActionType, &str> =
+            Arguments::parse_borrowed(&env_args, TestErrorType::Syntax).unwrap();
+        let value = parsed.flags[0].value.unwrap();
+        assert_eq!(value, "borrowed");
+        // The value points into the source string rather than a copy
+        assert_eq!(value.as_ptr(), source[0][3..].as_ptr());
+        assert_eq!(parsed.all_values_of(TestFlagType::Print), vec!["'x'"]);
+        assert_eq!(parsed.summary(), "flags=[Help=borrowed, Print='x'] actions=[Add]");
+    }
+    // The source is still usable once the parsed arguments are gone
+    assert_eq!(source.len(), 3);
+}
+
+#[test]
+fn parse_cow_values() {
+    let source = String::from("--level=2");
+    let env_args = vec![source.as_str(), "-v", "edit"];
+    let mut parsed: Arguments<TestFlagType, TestActionType, Cow<str>> =
+        Arguments::parse_cow(&env_args, TestErrorType::Syntax).unwrap();
+    // Parsed values borrow from the input instead of allocating
+    assert!(matches!(parsed.flags[0].value, Some(Cow::Borrowed(value)) if value.as_ptr() == source[8..].as_ptr()));
+    parsed.flags[1].value = Some(Cow::Owned(1.to_string()));
+    assert_eq!(parsed.summary(), "flags=[Level=2, Verbose=1] actions=[Edit]");
+}
+
+#[test]
+fn greedy_values() {
+    let options = ParseOptions::new().greedy_values(true);
+    let env_args =
+        TestArguments::parse_with(vec!["--files", "a", "b", "-v", "add"], TestErrorType::Syntax, &options)
+            .unwrap();
+    assert_eq!(env_args.flags[0].key, TestFlagType::Files);
+    assert_eq!(env_args.flags[0].value, None);
+    assert_eq!(env_args.flags[0].values, vec!["a".to_string(), "b".to_string()]);
+    // `-v` takes no value, so `add` is still an action
+    assert_eq!(env_args.flags[1].key, TestFlagType::Verbose);
+    assert!(env_args.flags[1].values.is_empty());
+    assert_eq!(env_args.actions, vec![TestActionType::Add]);
+    assert_eq!(
+        env_args.to_json(),
+        r#"{"flags":[{"key":"Files","value":null,"values":["a","b"]},{"key":"Verbose","value":null}],"actions":["Add"]}"#
+    );
+
+    // Without the option the same arguments are actions
+    assert_eq!(
+        TestArguments::parse(vec!["--files", "add", "remove"], TestErrorType::Syntax).unwrap().actions.len(),
+        2
+    );
+}
+
+#[test]
+fn parse_with_lazy_error() {
+    let calls = Cell::new(0);
+    let make_error = |token: &str| {
+        calls.set(calls.get() + 1);
+        TestMessageError(format!("bad argument '{}'", token))
+    };
+
+    let env_args = TestArguments::parse_with_err(vec!["-v", "add", "-h=x"], make_error).unwrap();
+    assert_eq!(env_args.flags.len(), 2);
+    assert_eq!(calls.get(), 0);
+
+    let failure = TestArguments::parse_with_err(vec!["-v", "frobnicate", "--bogus"], make_error).unwrap_err();
+    assert_eq!(failure.as_str(), "bad argument 'frobnicate'");
+    assert_eq!(calls.get(), 1);
+}
+
+#[derive(Debug, Clone)]
+enum TestDynamicError {
+    Unknown(String),
+}
+
+impl Error for TestDynamicError {
+    fn handle(&self) {
+        println!("{}", self.message());
+    }
+    fn as_str(&self) -> &str {
+        "Unknown argument"
+    }
+    fn message(&self) -> Cow<'_, str> {
+        match self {
+            Self::Unknown(token) => Cow::Owned(format!("Unknown argument '{}'", token)),
+        }
+    }
+}
+
+#[test]
+fn error_messages() {
+    // Static messages fall back to `as_str`
+    assert_eq!(TestErrorType::Syntax.message(), "Improper syntax usage");
+    let failure = TestArguments::parse_with(vec!["add", "-q"], TestErrorType::Syntax, &ParseOptions::new())
+        .unwrap_err();
+    assert_eq!(failure.to_string(), "Improper syntax usage (argument 1: '-q')");
+
+    let make_error = |token: &str| TestDynamicError::Unknown(token.to_string());
+    let failure = TestArguments::parse_with_err(vec!["add", "-q"], make_error).unwrap_err();
+    assert_eq!(failure.message(), "Unknown argument '-q'");
+    let failure = ParseFailure::new(failure, 1, "-q");
+    assert_eq!(failure.to_string(), "Unknown argument '-q' (argument 1: '-q')");
+}
+
+#[test]
+fn failure_spans() {
+    let options = ParseOptions::new().positionals(PositionalPolicy::default());
+    let env_args = vec!["héllo", "add", "-q"];
+    let failure = simulate_with(env_args.clone(), &options).unwrap_err();
+    assert_eq!(failure.index, 2);
+    assert_eq!(failure.span, 11..13);
+    assert_eq!(failure.render_caret(&env_args.join(" ")), "héllo add -q\n          ^^");
+
+    let mut items = TestArguments::parse_iter(vec!["-v", "-p=x", "-q"], TestErrorType::Syntax);
+    let failure = items.find_map(Result::err).unwrap();
+    assert_eq!(failure.span, 8..10);
+
+    // A missing action is pointed at just past the end
+    let options = ParseOptions::new().action_policy(ActionPolicy::ExactlyOne);
+    let failure = simulate_with(vec!["-v", "-p"], &options).unwrap_err();
+    assert_eq!(failure.span, 5..5);
+    assert_eq!(failure.render_caret("-v -p"), "-v -p\n     ^");
+}
+
+#[test]
+fn render_failure() {
+    let options = ParseOptions::new();
+    let failure = TestArguments::parse_with(vec!["add", "-q"], TestErrorType::Syntax, &options).unwrap_err();
+    let plain = "error: Improper syntax usage (argument 1: '-q')";
+    assert_eq!(format!("error: {}", failure), plain);
+    #[cfg(not(feature = "color"))]
+    assert_eq!(failure.render(), plain);
+    #[cfg(feature = "color")]
+    {
+        use crate::ColorChoice;
+        assert_eq!(
+            failure.render_with(ColorChoice::Always),
+            "error: \x1b[31mImproper syntax usage\x1b[0m (argument 1: '\x1b[1m-q\x1b[0m')"
+        );
+        assert_eq!(failure.render_with(ColorChoice::Never), plain);
+        assert!(TestArguments::usage_with(ColorChoice::Always)
+            .starts_with("Flags:\n  \x1b[1m-h\x1b[0m, \x1b[1m--help\x1b[0m\n"));
+        let usage = TestArguments::usage_with(ColorChoice::Never);
+        assert_eq!(usage, TestArguments::usage_painted(false, &Messages::ENGLISH));
+        assert!(!usage.contains('\x1b'));
+    }
+}
+
+#[cfg(feature = "color")]
+#[test]
+fn resolve_color_choice() {
+    use crate::ColorChoice;
+    use std::ffi::OsStr;
+    assert!(ColorChoice::Auto.resolve_with(true, None));
+    assert!(ColorChoice::Auto.resolve_with(true, Some(OsStr::new(""))));
+    assert!(!ColorChoice::Auto.resolve_with(true, Some(OsStr::new("1"))));
+    assert!(!ColorChoice::Auto.resolve_with(false, None));
+    assert!(ColorChoice::Always.resolve(true) && ColorChoice::Always.resolve(false));
+    assert!(ColorChoice::Always.resolve_with(false, Some(OsStr::new("1"))));
+    assert!(!ColorChoice::Never.resolve(true));
+    assert!(!ColorChoice::Auto.resolve(false));
+}
+
+#[test]
+fn translated_messages() {
+    const GERMAN: Messages = Messages {
+        error_prefix: "Fehler: ",
+        argument: "Argument",
+        did_you_mean: "meinten Sie",
+        flags_heading: "Optionen:",
+        actions_heading: "Aktionen:",
+        unknown_flag: "Unbekannte Option",
+        ..Messages::ENGLISH
+    };
+    let options = ParseOptions::new().messages(&GERMAN);
+    let failure = TestArguments::parse_with(vec!["add", "-q"], ParseError::UnknownFlag, &options)
+        .unwrap_err();
+    assert_eq!(failure.to_string(), "Unbekannte Option (Argument 1: '-q')");
+    #[cfg(not(feature = "color"))]
+    assert_eq!(failure.render(), "Fehler: Unbekannte Option (Argument 1: '-q')");
+
+    // A user error keeps its own message
+    let failure =
+        TestArguments::parse_with(vec!["-q"], TestErrorType::Syntax, &options).unwrap_err();
+    assert_eq!(failure.to_string(), "Improper syntax usage (Argument 0: '-q')");
+
+    let options = options.allow_abbreviation(true);
+    let failure = Arguments::<TestAbbrevFlag, TestActionType>::parse_with(
+        vec!["--ver"],
+        ParseError::UnknownFlag,
+        &options,
+    )
+    .unwrap_err();
+    assert_eq!(
+        failure.to_string(),
+        "Unbekannte Option (Argument 0: '--ver'); meinten Sie --verb, --verbose, --version?"
+    );
+
+    let usage = TestArguments::usage_for(&options);
+    assert!(usage.starts_with("Optionen:\n"));
+    assert!(usage.contains("\nAktionen:\n"));
+    assert_eq!(TestArguments::usage_for(&ParseOptions::new()), TestArguments::usage());
+}
+
+#[test]
+fn last_value_wins() {
+    let env_args = simulate(vec!["--level=1", "add", "--level=2", "--level", "-h=x"]).unwrap_or_default();
+    assert_eq!(env_args.last_value_of(TestFlagType::Level), Some("2"));
+    assert_eq!(env_args.last_value_of(TestFlagType::Help), Some("x"));
+    assert_eq!(env_args.last_value_of(TestFlagType::Print), None);
+}
+
+#[test]
+fn render_round_trip() {
+    let env_args = simulate(vec!["--verbose", "a", "--level=2", "--print=x=y", "r"]).unwrap_or_default();
+    assert_eq!(env_args.flags[1].render(), "--level=2");
+    let rendered = env_args.to_args();
+    assert_eq!(rendered, vec!["--verbose", "--level=2", "--print=x=y", "add", "remove"]);
+    let reparsed = simulate(rendered.iter().map(String::as_str).collect()).unwrap_or_default();
+    assert_eq!(reparsed, env_args);
+}
+
+#[test]
+fn default_values() {
+    let defaults = [(TestFlagType::Print, Some("out")), (TestFlagType::Verbose, None)];
+
+    let env_args = simulate(vec!["add"]).unwrap_or_default().with_defaults(&defaults);
+    assert_eq!(env_args.get(TestFlagType::Print), FlagMatch::Value("out"));
+    assert_eq!(env_args.get(TestFlagType::Verbose), FlagMatch::Present);
+
+    let env_args = simulate(vec!["--print=log"]).unwrap_or_default().with_defaults(&defaults);
+    assert_eq!(env_args.all_values_of(TestFlagType::Print), vec!["log"]);
+
+    // An explicit empty value still counts as passed
+    let env_args = simulate(vec!["--print="]).unwrap_or_default().with_defaults(&defaults);
+    assert_eq!(env_args.all_values_of(TestFlagType::Print), vec![""]);
+    assert_eq!(env_args.flags.len(), 2);
+}
+
+#[test]
+fn slice_accessors() {
+    let env_args = simulate(vec!["-v", "add", "--level=1", "edit"]).unwrap_or_default();
+    assert_eq!(env_args.actions(), &[TestActionType::Add, TestActionType::Edit]);
+    assert_eq!(env_args.flags().len(), 2);
+    assert_eq!(env_args.flags()[1].value.as_deref(), Some("1"));
+    assert!(TestArguments::new().flags().is_empty());
+}
+
+#[test]
+fn reject_duplicate_flags() {
+    let env_args = simulate(vec!["-v", "add", "--verbose"]).unwrap_or_default();
+    assert!(matches!(env_args.reject_duplicates(TestErrorType::Syntax), Err(TestErrorType::Syntax)));
+    let env_args = simulate(vec!["-v", "--level=1"]).unwrap_or_default();
+    assert!(env_args.reject_duplicates(TestErrorType::Syntax).is_ok());
+}
+
+#[test]
+fn flag_match_states() {
+    let env_args = simulate(vec!["--level=1", "-v", "--level", "add"]).unwrap_or_default();
+    assert_eq!(env_args.get(TestFlagType::Level), FlagMatch::Value("1"));
+    assert_eq!(env_args.get(TestFlagType::Verbose), FlagMatch::Present);
+    assert_eq!(env_args.get(TestFlagType::Print), FlagMatch::Absent);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn flags_to_map() {
+    let env_args = simulate(vec!["-h=x", "add", "--level=1", "-v", "--level=2"]).unwrap_or_default();
+    let map = env_args.to_map();
+    assert_eq!(map.len(), 3);
+    assert_eq!(map[&TestFlagType::Help], Some("x".to_string()));
+    assert_eq!(map[&TestFlagType::Verbose], None);
+    assert_eq!(map[&TestFlagType::Level], Some("2".to_string()));
+    assert!(!map.contains_key(&TestFlagType::Print));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TestKeyFlag {
+    Out,
+}
+
+impl ParseKey for TestKeyFlag {
+    type Err = TestMessageError;
+    fn parse_key(key: &str) -> Result<Self, TestMessageError> {
+        match key {
+            "-o" | "--out" => Ok(Self::Out),
+            _ => Err(TestMessageError(format!("unknown flag {}", key))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TestKeyAction {
+    Build,
+}
+
+impl ParseKey for TestKeyAction {
+    type Err = TestMessageError;
+    fn parse_key(key: &str) -> Result<Self, TestMessageError> {
+        match key {
+            "build" => Ok(Self::Build),
+            _ => Err(TestMessageError(format!("unknown action {}", key))),
+        }
+    }
+}
+
+#[test]
+fn parse_with_parse_key() {
+    let env_args = Arguments::<TestKeyFlag, TestKeyAction>::parse2(vec!["build", "-o=a=b"]).unwrap();
+    assert_eq!(env_args.flags[0].key, TestKeyFlag::Out);
+    assert_eq!(env_args.flags[0].value, Some("a=b".to_string()));
+    assert_eq!(env_args.actions, vec![TestKeyAction::Build]);
+
+    let failure = Arguments::<TestKeyFlag, TestKeyAction>::parse2(vec!["build", "--in"]).unwrap_err();
+    assert_eq!(failure.as_str(), "unknown flag --in");
+    let failure = Arguments::<TestKeyFlag, TestKeyAction>::parse2(vec!["test"]).unwrap_err();
+    assert_eq!(failure.as_str(), "unknown action test");
+}
+
+#[test]
+fn parse_key_from_argument_type() {
+    assert_eq!(TestFlagType::parse_key("--verbose"), Ok(TestFlagType::Verbose));
+    assert_eq!(TestActionType::parse_key("-x"), Err(ParseError::UnknownFlag));
+
+    let env_args = TestArguments::parse2(vec!["-v", "add", "--level=2"]).unwrap();
+    assert_eq!(env_args.flags[1].value, Some("2".to_string()));
+    assert_eq!(env_args.actions, vec![TestActionType::Add]);
+    assert_eq!(TestArguments::parse2(vec!["-v", "frob"]).unwrap_err(), ParseError::UnknownAction);
+    assert_eq!(TestArguments::parse2(vec!["--bogus"]).unwrap_err(), ParseError::UnknownFlag);
+}
+
+#[test]
+fn parse2_matches_parse() {
+    let lines = [
+        vec!["-v=x"],
+        vec!["add", ""],
+        vec!["-"],
+        vec!["-v", "--"],
+        vec!["--print=a=b", "-h", "edit"],
+        vec!["-v", "frob"],
+    ];
+    for line in lines.iter() {
+        let expected = TestArguments::parse(line.clone(), ParseError::UnknownFlag);
+        assert_eq!(TestArguments::parse2(line.clone()), expected);
+    }
+    assert_eq!(TestArguments::parse2(vec!["-v=x"]).unwrap_err(), ParseError::UnexpectedValue);
+    assert_eq!(TestArguments::parse2(vec!["add", ""]).unwrap_err(), ParseError::EmptyArg);
+    assert_eq!(TestArguments::parse2(vec!["-"]).unwrap_err(), ParseError::UnknownAction);
+    assert_eq!(TestArguments::parse2(vec!["--"]).unwrap_err(), ParseError::UnknownFlag);
+
+    // A type's own error is kept for the same arguments
+    type KeyArguments = Arguments<TestKeyFlag, TestKeyAction>;
+    assert_eq!(KeyArguments::parse2(vec!["build", "-"]).unwrap_err().as_str(), "unknown action -");
+    assert_eq!(KeyArguments::parse2(vec!["--"]).unwrap_err().as_str(), "unknown flag --");
+    assert_eq!(KeyArguments::parse2(vec![""]).unwrap_err().as_str(), "unknown action ");
+    assert_eq!(KeyArguments::parse2(vec!["-o=x"]).unwrap().flags[0].value, Some("x".to_string()));
+}
+
+#[test]
+fn value_containing_separator() {
+    let env_args = simulate(vec!["--print=key=value", "-h=="]).unwrap_or_default();
+    assert_eq!(env_args.flags[0].key, TestFlagType::Print);
+    assert_eq!(env_args.flags[0].value, Some("key=value".to_string()));
+    assert_eq!(env_args.flags[1].value, Some("=".to_string()));
+}
+
+#[test]
+fn variants_and_usage() {
+    // The first known key of each variant, skipping `TestFail` which has none
+    let keys: Vec<&str> = TestFlagType::variants()
+        .iter()
+        .filter_map(|variant| {
+            TestFlagType::known_keys()
+                .iter()
+                .find(|key| matches!(TestFlagType::from_str(key, TestErrorType::Syntax), Ok(v) if v == *variant))
+        })
+        .copied()
+        .collect();
+    assert_eq!(keys, vec!["-h", "-v", "-p", "-l", "-f"]);
+    assert_eq!(
+        TestArguments::usage(),
+        "Flags:\n  -h, --help\n  -v, --verbose\n  -p, --print\n  -l, --level\n  -f, --files\n\n\
+         Actions:\n  add, a\n  remove, r\n  edit, e\n"
+    );
+}
+
+// Records the key and style the parser hands over
+#[derive(Debug, Clone, PartialEq)]
+struct TestStyleKey(String, KeyStyle);
+
+impl ArgumentType for TestStyleKey {
+    fn from_str<E: Error>(_key: &str, error: E) -> Result<Self, E> {
+        Err(error)
+    }
+    fn from_key<E: Error>(key: &str, style: KeyStyle, _error: E) -> Result<Self, E> {
+        Ok(TestStyleKey(key.to_string(), style))
+    }
+}
+
+#[test]
+fn strip_prefix_from_keys() {
+    let options = ParseOptions::new().strip_prefix(true);
+    let env_args: Arguments<TestStyleKey, TestStyleKey> =
+        Arguments::parse_with(vec!["-h", "--help=x", "add"], TestErrorType::Syntax, &options).unwrap();
+    assert_eq!(env_args.flags[0].key, TestStyleKey("h".to_string(), KeyStyle::Short));
+    assert_eq!(env_args.flags[1].key, TestStyleKey("help".to_string(), KeyStyle::Long));
+    assert_eq!(env_args.flags[1].value, Some("x".to_string()));
+    assert_eq!(env_args.actions, vec![TestStyleKey("add".to_string(), KeyStyle::Action)]);
+
+    // Types that only implement `from_str` get the raw tokens back
+    let env_args = TestArguments::parse_with(vec!["-h", "--verbose", "add"], TestErrorType::Syntax, &options).unwrap();
+    assert_eq!(env_args.flags[1].key, TestFlagType::Verbose);
+    assert_eq!(env_args.actions, vec![TestActionType::Add]);
+    // Without the option, `from_key` is never called
+    assert!(Arguments::<TestStyleKey, TestStyleKey>::parse(vec!["-h"], TestErrorType::Syntax).is_err());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TestToggleFlag {
+    Color,
+    NoCache,
+}
+
+impl ArgumentType for TestToggleFlag {
+    fn from_str<E: Error>(key: &str, error: E) -> Result<Self, E> {
+        match key {
+            "--color" => Ok(Self::Color),
+            "--no-cache" => Ok(Self::NoCache),
+            _ => Err(error),
+        }
+    }
+}
+
+#[test]
+fn negated_flags() {
+    let options = ParseOptions::new().negation_prefix("no-");
+    let parse = |env_args| {
+        Arguments::<TestToggleFlag, TestActionType>::parse_with(env_args, TestErrorType::Syntax, &options)
+    };
+    let env_args = parse(vec!["--color", "--no-color", "--no-cache"]).unwrap();
+    assert_eq!(env_args.flags[0].key, TestToggleFlag::Color);
+    assert_eq!(env_args.flags[0].value, None);
+    assert_eq!(env_args.flags[1].key, TestToggleFlag::Color);
+    assert_eq!(env_args.flags[1].value, Some("false".to_string()));
+    // A direct match takes precedence over the rewrite
+    assert_eq!(env_args.flags[2].key, TestToggleFlag::NoCache);
+    assert_eq!(env_args.flags[2].value, None);
+
+    let failure = parse(vec!["add", "--no-shadow"]).unwrap_err();
+    assert_eq!(failure.token, "--no-shadow");
+    // Without the option nothing is rewritten
+    assert!(Arguments::<TestToggleFlag, TestActionType>::parse(vec!["--no-color"], TestErrorType::Syntax).is_err());
+}
+
+#[test]
+fn completion_scripts() {
+    let bash = TestArguments::bash_completion("my-tool");
+    let zsh = TestArguments::zsh_completion("my-tool");
+    let keys: Vec<&str> = TestFlagType::known_keys().iter().chain(TestActionType::known_keys()).copied().collect();
+    assert!(keys.contains(&"--files") && keys.contains(&"edit"));
+    assert!(bash.contains(&format!("compgen -W \"{}\"", keys.join(" "))));
+    assert!(zsh.contains(&format!("keys=({})", keys.join(" "))));
+    assert!(bash.ends_with("complete -F _my_tool my-tool\n"));
+    assert!(zsh.starts_with("#compdef my-tool\n"));
+}
+
+#[test]
+fn colon_separator() {
+    let options = ParseOptions::new().value_separator(':');
+    let env_args = TestArguments::parse_with(vec!["--level:2", "-h:a=b", "add"], TestErrorType::Syntax, &options).unwrap();
+    assert_eq!(env_args.flags[0].key, TestFlagType::Level);
+    assert_eq!(env_args.flags[0].value, Some("2".to_string()));
+    // `=` is now part of the value
+    assert_eq!(env_args.flags[1].value, Some("a=b".to_string()));
+    assert!(TestArguments::parse_with(vec!["--level=2"], TestErrorType::Syntax, &options).is_err());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TestAbbrevFlag {
+    Verb,
+    Verbose,
+    Version,
+}
+
+impl ArgumentType for TestAbbrevFlag {
+    fn from_str<E: Error>(key: &str, error: E) -> Result<Self, E> {
+        match key {
+            "--verb" => Ok(Self::Verb),
+            "--verbose" => Ok(Self::Verbose),
+            "--version" => Ok(Self::Version),
+            _ => Err(error),
+        }
+    }
+    fn known_keys() -> &'static [&'static str] {
+        &["--verb", "--verbose", "--version"]
+    }
+}
+
+#[test]
+fn abbreviated_flags() {
+    let options = ParseOptions::new().allow_abbreviation(true);
+    let parse = |env_args| {
+        Arguments::<TestAbbrevFlag, TestActionType>::parse_with(env_args, TestErrorType::Syntax, &options)
+    };
+    let env_args = parse(vec!["--verbo", "--vers=2", "--verb"]).unwrap();
+    assert_eq!(env_args.flags[0].key, TestAbbrevFlag::Verbose);
+    assert_eq!(env_args.flags[1].key, TestAbbrevFlag::Version);
+    assert_eq!(env_args.flags[1].value, Some("2".to_string()));
+    // An exact match wins even though it also abbreviates `--verbose`
+    assert_eq!(env_args.flags[2].key, TestAbbrevFlag::Verb);
+
+    let failure = parse(vec!["add", "--ver"]).unwrap_err();
+    assert_eq!((failure.index, failure.token.as_str()), (1, "--ver"));
+    assert_eq!(failure.candidates, vec!["--verb", "--verbose", "--version"]);
+    assert!(parse(vec!["--frob"]).unwrap_err().candidates.is_empty());
+    let plain = "Improper syntax usage (argument 1: '--ver'); did you mean --verb, --verbose, --version?";
+    assert_eq!(failure.to_string(), plain);
+    #[cfg(not(feature = "color"))]
+    assert_eq!(failure.render(), format!("error: {}", plain));
+    #[cfg(feature = "color")]
+    {
+        use crate::ColorChoice;
+        assert_eq!(failure.render_with(ColorChoice::Never), format!("error: {}", plain));
+        assert_eq!(
+            failure.render_with(ColorChoice::Always),
+            "error: \x1b[31mImproper syntax usage\x1b[0m (argument 1: '\x1b[1m--ver\x1b[0m'); did you mean \
+             \x1b[33m--verb\x1b[0m, \x1b[33m--verbose\x1b[0m, \x1b[33m--version\x1b[0m?"
+        );
+    }
+    // `--` is looked up as it is rather than abbreviating every long key
+    assert_eq!(parse(vec!["--", "add"]).unwrap_err().index, 0);
+    assert!(parse(vec!["--verbosity"]).is_err());
+    assert!(Arguments::<TestAbbrevFlag, TestActionType>::parse(vec!["--verbo"], TestErrorType::Syntax).is_err());
+
+    // Two keys of the same flag aren't ambiguous
+    let env_args = Arguments::<TestAbbrevAliasFlag, TestActionType>::parse_with(
+        vec!["--verb", "--"],
+        TestErrorType::Syntax,
+        &options,
+    );
+    assert_eq!(env_args.unwrap_err().index, 1);
+    let env_args = Arguments::<TestAbbrevAliasFlag, TestActionType>::parse_with(
+        vec!["--verb"],
+        TestErrorType::Syntax,
+        &options,
+    );
+    assert_eq!(env_args.unwrap().flags[0].key, TestAbbrevAliasFlag::Verbose);
+}
+
+crate::arg_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum TestAbbrevAliasFlag {
+        Verbose => ["--verbose", "--verbosity"],
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TestDefineFlag {
+    Define,
+    Verbose,
+}
+
+impl ArgumentType for TestDefineFlag {
+    fn from_str<E: Error>(key: &str, error: E) -> Result<Self, E> {
+        match key {
+            "-D" => Ok(Self::Define),
+            "-v" => Ok(Self::Verbose),
+            _ => Err(error),
+        }
+    }
+    fn is_map(&self) -> bool {
+        matches!(self, Self::Define)
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn map_flag_values() {
+    let env_args = Arguments::<TestDefineFlag, TestActionType>::parse(
+        vec!["-Dname=app", "-v", "-D=mode=fast", "-Dname=lib", "-Ddebug", "add"],
+        TestErrorType::Syntax,
+    )
+    .unwrap();
+    assert_eq!(env_args.flags[0].value, Some("name=app".to_string()));
+    let map = env_args.map_values(TestDefineFlag::Define);
+    assert_eq!(map.len(), 3);
+    // Repeated names keep the last value
+    assert_eq!(map["name"], "lib");
+    assert_eq!(map["mode"], "fast");
+    assert_eq!(map["debug"], "");
+    assert!(env_args.map_values(TestDefineFlag::Verbose).is_empty());
+}
+
+#[test]
+fn map_flag_requires_pairs() {
+    let options = ParseOptions::new().require_map_pairs(true);
+    let parse = |env_args| {
+        Arguments::<TestDefineFlag, TestActionType>::parse_with(env_args, TestErrorType::Syntax, &options)
+    };
+    assert!(parse(vec!["-Dname=app"]).is_ok());
+    assert_eq!(parse(vec!["-Dname=app", "-Ddebug"]).unwrap_err().index, 1);
+    assert_eq!(parse(vec!["-D=debug"]).unwrap_err().index, 0);
+    // Other flags still can't have anything attached
+    assert!(parse(vec!["-vx"]).is_err());
+}
+
+#[test]
+fn flag_styles() {
+    let options = ParseOptions::new().short_clusters(true);
+    let env_args = simulate_with(vec!["-v", "--verbose", "--help=x", "-hv"], &options).unwrap();
+    let styles: Vec<FlagStyle> = env_args.flags.iter().map(|flag| flag.style).collect();
+    assert_eq!(
+        styles,
+        vec![FlagStyle::Short, FlagStyle::Long, FlagStyle::Long, FlagStyle::Short, FlagStyle::Short]
+    );
+}
+
+#[test]
+fn require_single_action() {
+    let required = |env_args| {
+        let env_args = simulate(env_args).unwrap_or_default();
+        env_args.require_action(ParseError::UnknownAction).copied()
+    };
+    assert_eq!(required(vec!["-v"]), Err(ParseError::UnknownAction));
+    assert_eq!(required(vec!["-v", "edit"]), Ok(TestActionType::Edit));
+    assert_eq!(required(vec!["add", "-v", "edit"]), Err(ParseError::UnknownAction));
+}
+
+#[test]
+fn positional_arity() {
+    let options = ParseOptions::new().positionals(PositionalPolicy { min: 1, max: Some(2) });
+    let error = TestMessageError("parse".to_string());
+    let parse = |env_args| TestArguments::parse_with(env_args, error.clone(), &options);
+
+    let env_args = parse(vec!["add", "a.txt", "-v", "--", "-b.txt"]).unwrap();
+    assert_eq!(env_args.actions, vec![TestActionType::Add]);
+    assert_eq!(env_args.positionals, vec!["a.txt".to_string(), "-b.txt".to_string()]);
+    assert_eq!(
+        env_args.to_json(),
+        r#"{"flags":[{"key":"Verbose","value":null}],"actions":["Add"],"positionals":["a.txt","-b.txt"]}"#
+    );
+
+    let failure = parse(vec!["add", "-v"]).unwrap_err();
+    assert_eq!((failure.index, failure.token.as_str()), (2, ""));
+    assert_eq!(failure.error.as_str(), "parse: 0 positionals");
+
+    let failure = parse(vec!["x", "y", "--", "z", "w"]).unwrap_err();
+    assert_eq!((failure.index, failure.token.as_str()), (3, "z"));
+    assert_eq!(failure.error.as_str(), "parse: 4 positionals");
+
+    // Without a policy, unknown actions still fail
+    assert!(simulate(vec!["add", "a.txt"]).is_err());
+}
+
+#[test]
+fn common_flags() {
+    let env_args = Arguments::<CommonFlags, TestActionType>::parse(
+        vec!["-h", "--help", "-V", "--version", "-v", "--verbose", "-q", "--quiet", "add"],
+        ParseError::EmptyArg,
+    )
+    .unwrap();
+    let keys: Vec<CommonFlags> = env_args.flags.iter().map(|flag| flag.key).collect();
+    let expected: Vec<CommonFlags> = CommonFlags::variants().iter().flat_map(|&k| [k, k]).collect();
+    assert_eq!(keys, expected);
+    assert_eq!(env_args.actions, vec![TestActionType::Add]);
+    assert!(Arguments::<CommonFlags, TestActionType>::parse(vec!["-q=x"], ParseError::EmptyArg).is_err());
+
+    // Every known key parses back into a variant
+    for key in CommonFlags::known_keys() {
+        assert!(CommonFlags::from_str(key, ParseError::UnknownFlag).is_ok());
+    }
+    assert!(Arguments::<CommonFlags, TestActionType>::usage().contains("  -V, --version  Print version\n"));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TestWrappedFlag {
+    Output,
+    Common(CommonFlags),
+}
+
+impl ArgumentType for TestWrappedFlag {
+    fn from_str<E: Error>(key: &str, error: E) -> Result<Self, E> {
+        match key {
+            "-o" | "--output" => Ok(Self::Output),
+            _ => CommonFlags::from_str(key, error).map(Self::Common),
+        }
+    }
+}
+
+#[test]
+fn delegate_to_common_flags() {
+    let env_args =
+        Arguments::<TestWrappedFlag, TestActionType>::parse(vec!["-o=out", "-v"], TestErrorType::Syntax).unwrap();
+    assert_eq!(env_args.flags[0].key, TestWrappedFlag::Output);
+    assert_eq!(env_args.flags[1].key, TestWrappedFlag::Common(CommonFlags::Verbose));
+    assert!(Arguments::<TestWrappedFlag, TestActionType>::parse(vec!["-x"], TestErrorType::Syntax).is_err());
+}
+
+// Records the token it's handled with
+#[derive(Clone)]
+struct TestRecordingError<'a>(&'a RefCell<String>);
+
+impl Error for TestRecordingError<'_> {
+    fn handle(&self) {}
+    fn handle_with(&self, token: &str) {
+        self.0.replace(token.to_string());
+    }
+    fn as_str(&self) -> &str {
+        "Recorded"
+    }
+}
+
+#[test]
+fn handle_with_token() {
+    let seen = RefCell::new(String::new());
+    let options = ParseOptions::new().handle_errors(true);
+    let result = TestArguments::parse_with(vec!["add", "--frob"], TestRecordingError(&seen), &options);
+    assert!(result.is_err());
+    assert_eq!(*seen.borrow(), "--frob");
+
+    // Errors are only handled when asked to
+    let seen = RefCell::new(String::new());
+    assert!(TestArguments::parse(vec!["--frob"], TestRecordingError(&seen)).is_err());
+    assert_eq!(*seen.borrow(), "");
+}
+
+// Drifted out of sync: `-x` has no variant, and `Remove` renders as `Add`'s key
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TestDriftedAction {
+    Add,
+    Remove,
+}
+
+impl ArgumentType for TestDriftedAction {
+    fn from_str<E: Error>(key: &str, error: E) -> Result<Self, E> {
+        match key {
+            "add" => Ok(Self::Add),
+            "remove" | "rm" => Ok(Self::Remove),
+            _ => Err(error),
+        }
+    }
+    fn known_keys() -> &'static [&'static str] {
+        &["add", "remove", "rm", "-x"]
+    }
+    fn canonical_key(&self) -> &'static str {
+        "add"
+    }
+}
+
+#[test]
+fn round_trip_check() {
+    assert_eq!(check::round_trip::<TestFlagType, _>(TestErrorType::Syntax), Ok(()));
+    assert_eq!(check::round_trip::<CommonFlags, _>(TestErrorType::Syntax), Ok(()));
+    assert_eq!(
+        check::round_trip::<TestDriftedAction, _>(TestErrorType::Syntax),
+        Err(vec![
+            "'remove' renders as 'add', which parses differently".to_string(),
+            "'rm' renders as 'add', which parses differently".to_string(),
+            "'-x' doesn't parse".to_string(),
+        ])
+    );
+}
+
+#[test]
+fn validate_reports_every_rule() {
+    let rules = [
+        Rule::RequiredFlag(TestFlagType::Files),
+        Rule::RequiresValue(TestFlagType::Level),
+        Rule::Conflicts(vec![TestFlagType::Help, TestFlagType::Verbose, TestFlagType::Print]),
+        Rule::Requires(TestFlagType::Print, TestFlagType::Level),
+        Rule::ExactlyOneAction,
+        Rule::MaxPositionals(0),
+    ];
+    let env_args = simulate(vec!["-h", "-v", "--level", "add", "edit"]).unwrap_or_default();
+    let report = env_args.validate(&rules).unwrap_err();
+    let violated: Vec<&Rule<TestFlagType>> = report.violations.iter().map(|(rule, _)| rule).collect();
+    assert_eq!(violated, vec![&rules[0], &rules[1], &rules[2], &rules[4]]);
+    assert_eq!(
+        report.to_string(),
+        "missing required flag '--files'\n\
+         '--level' requires a value\n\
+         '--help' conflicts with '--verbose'\n\
+         expected exactly one action, found 2"
+    );
+
+    let env_args = simulate(vec!["--files=x", "--level=1", "-p", "add"]).unwrap_or_default();
+    assert_eq!(env_args.validate(&rules), Ok(()));
+}
+
+#[test]
+fn classify_tokens() {
+    let options = ParseOptions::new();
+    assert_eq!(classify("-v", &options), TokenKind::ShortFlag { key: "-v", value: None });
+    assert_eq!(classify("-l=2", &options), TokenKind::ShortFlag { key: "-l", value: Some("2") });
+    assert_eq!(classify("--verbose", &options), TokenKind::LongFlag { key: "--verbose", value: None });
+    assert_eq!(classify("--k=v=w", &options), TokenKind::LongFlag { key: "--k", value: Some("v=w") });
+    assert_eq!(classify("--k=", &options), TokenKind::LongFlag { key: "--k", value: Some("") });
+    assert_eq!(classify("--", &options), TokenKind::Terminator);
+    assert_eq!(classify("-", &options), TokenKind::StdinDash);
+    assert_eq!(classify("add", &options), TokenKind::Word);
+    assert_eq!(classify("", &options), TokenKind::Word);
+    assert_eq!(classify("a=b", &options), TokenKind::Word);
+    assert_eq!(classify("-é", &options), TokenKind::ShortFlag { key: "-é", value: None });
+    assert_eq!(classify("--naïve=ü", &options), TokenKind::LongFlag { key: "--naïve", value: Some("ü") });
+
+    let options = ParseOptions::new().value_separator(':').strip_quotes(true);
+    assert_eq!(classify("--k:'v'", &options), TokenKind::LongFlag { key: "--k", value: Some("v") });
+    assert_eq!(classify("--k='v'", &options), TokenKind::LongFlag { key: "--k='v'", value: None });
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TestGotoAction {
+    Quit,
+    Goto(usize),
+}
+
+impl ArgumentType for TestGotoAction {
+    fn from_str<E: Error>(key: &str, error: E) -> Result<Self, E> {
+        match key {
+            "quit" => Ok(Self::Quit),
+            _ => Err(error),
+        }
+    }
+    fn from_str_with_arg<E: Error>(key: &str, arg: Option<&str>, error: E) -> Result<Self, E> {
+        match (key, arg.map(str::parse)) {
+            ("goto", Some(Ok(line))) => Ok(Self::Goto(line)),
+            _ => Err(error),
+        }
+    }
+}
+
+#[test]
+fn action_with_argument() {
+    type GotoArguments = Arguments<TestFlagType, TestGotoAction>;
+    let env_args = GotoArguments::parse(vec!["goto", "5", "-v", "quit"], TestErrorType::Syntax).unwrap();
+    assert_eq!(env_args.actions, vec![TestGotoAction::Goto(5), TestGotoAction::Quit]);
+    assert_eq!(env_args.flags.len(), 1);
+
+    assert!(GotoArguments::parse(vec!["goto"], TestErrorType::Syntax).is_err());
+    assert!(GotoArguments::parse(vec!["goto", "x"], TestErrorType::Syntax).is_err());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn errors_propagate_with_question_mark() {
+    fn run(env_args: Vec<&str>) -> Result<usize, std::boxed::Box<dyn std::error::Error>> {
+        let args = TestArguments::parse_with(env_args, TestErrorType::Syntax, &ParseOptions::new())?;
+        let action = args.require_action(ParseError::UnknownAction)?;
+        Ok(args.flags.len() + (*action == TestActionType::Add) as usize)
+    }
+    assert_eq!(run(vec!["-v", "add"]).unwrap(), 2);
+    assert_eq!(run(vec!["--frob"]).unwrap_err().to_string(), "Improper syntax usage (argument 0: '--frob')");
+    assert_eq!(run(vec!["-v"]).unwrap_err().to_string(), "Unknown action");
+}
+
+// Builds an `Arguments` object from lazily parsed items, stopping at the
+// first error
+fn collect_items(
+    env_args: Vec<&str>,
+    options: &ParseOptions,
+) -> Result<TestArguments, ParseFailure<TestErrorType>> {
+    let mut args = TestArguments::new();
+    for item in TestArguments::parse_iter_with(env_args, TestErrorType::Syntax, options) {
+        match item? {
+            ParsedItem::Flag(flag) => args.flags.push(flag),
+            ParsedItem::Action(action) => args.actions.push(action),
+            ParsedItem::Positional(value) => args.positionals.push(value),
+            ParsedItem::Passthrough(key, value) => args.passthrough.push((key, value)),
+        }
+    }
+    Ok(args)
+}
+
+#[test]
+fn parse_iter_matches_parse() {
+    let lines = vec![
+        vec!["-v", "add", "--level=2", "-p=x=y", "r"],
+        vec!["--help", "-f="],
+        vec![],
+        vec!["add", "--frob", "edit"],
+        vec!["-v=x"],
+        vec!["--level=9"],
+        vec!["add", ""],
+        vec!["frobnicate"],
+    ];
+    for line in lines {
+        let expected = TestArguments::parse_with(line.clone(), TestErrorType::Syntax, &ParseOptions::new());
+        match (collect_items(line, &ParseOptions::new()), expected) {
+            (Ok(args), Ok(expected)) => assert_eq!(args, expected),
+            (Err(failure), Err(expected)) => assert_eq!(failure.index, expected.index),
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[test]
+fn parse_iter_with_options_matches_parse_with() {
+    let options = ParseOptions::new()
+        .short_clusters(true)
+        .greedy_values(true)
+        .negation_prefix("no-")
+        .allow_abbreviation(true)
+        .passthrough_pairs(true)
+        .aliases(&[("ad", &["add", "-v"])]);
+    let lines = [
+        vec!["-vh", "ad", "-f", "a", "b", "--lev=2"],
+        vec!["--no-print", "add", "--", "x=1", "y=2"],
+        vec!["-f", "-v", "-vx"],
+        vec!["add", "--", "x"],
+        vec!["-hl"],
+        vec!["remove", "-f"],
+    ];
+    let positional = PositionalPolicy { min: 0, max: Some(1) };
+    let with_positionals = options.clone().positionals(positional).passthrough_pairs(false);
+    for options in [options, with_positionals].iter() {
+        for line in lines.iter() {
+            let expected = TestArguments::parse_with(line.clone(), TestErrorType::Syntax, options);
+            let items = TestArguments::parse_iter_with(line.clone(), TestErrorType::Syntax, options);
+            let mut parser = Parser::with_options(TestErrorType::Syntax, options);
+            let pushed = line.iter().try_for_each(|token| parser.push(token).map(drop));
+            let pushed = pushed.and_then(|()| parser.finish());
+            match (collect_items(line.clone(), options), pushed, expected) {
+                (Ok(args), Ok(pushed), Ok(expected)) => {
+                    assert_eq!(args, expected);
+                    assert_eq!(pushed, expected);
+                }
+                (Err(failure), Err(pushed), Err(expected)) => {
+                    assert_eq!(failure.index, expected.index);
+                    assert_eq!(pushed.index, expected.index);
+                }
+                _ => unreachable!(),
+            }
+            let valid = TestArguments::validate_args_with(line, TestErrorType::Syntax, options);
+            assert_eq!(valid.is_ok(), items.collect::<Result<Vec<_>, _>>().is_ok());
+        }
+    }
+}
+
+#[test]
+fn parse_iter_stops_early() {
+    // Only the arguments reached so far are read
+    let read = Cell::new(0);
+    let tokens = ["add", "-v"].iter().copied().cycle().take(10_000);
+    let tokens = tokens.inspect(|_| read.set(read.get() + 1));
+    let mut items = TestArguments::parse_iter(tokens, TestErrorType::Syntax);
+    assert!(matches!(items.next(), Some(Ok(ParsedItem::Action(TestActionType::Add)))));
+    assert!(matches!(items.next(), Some(Ok(ParsedItem::Flag(_)))));
+    assert_eq!(read.get(), 2);
+
+    // Nothing is yielded after an error
+    let mut items = TestArguments::parse_iter(vec!["--frob", "add"], TestErrorType::Syntax);
+    assert!(matches!(items.next(), Some(Err(ParseFailure { index: 0, .. }))));
+    assert!(items.next().is_none());
+}
+
+#[test]
+fn push_parser_matches_parse() {
+    let line = vec!["-v", "goto", "5", "--level=2", "quit", "goto", "7"];
+    let mut parser = Parser::new(TestErrorType::Syntax);
+    let mut waited = Vec::new();
+    for (i, token) in line.iter().enumerate() {
+        if parser.push(token).unwrap().is_empty() {
+            waited.push(i);
+        }
+    }
+    assert_eq!(waited, vec![1, 5]);
+    let expected = Arguments::<TestFlagType, TestGotoAction>::parse(line, TestErrorType::Syntax).unwrap();
+    assert_eq!(parser.finish().unwrap(), expected);
+
+    // A dangling action fails where it started
+    let mut parser: Parser<TestFlagType, TestGotoAction, _> = Parser::new(TestErrorType::Syntax);
+    assert!(parser.push("quit").is_ok());
+    assert!(matches!(parser.push("goto"), Ok(items) if items.is_empty()));
+    assert!(matches!(parser.finish(), Err(ParseFailure { index: 1, .. })));
+
+    let mut parser: Parser<TestFlagType, TestGotoAction, _> = Parser::new(TestErrorType::Syntax);
+    assert!(matches!(parser.push("--frob"), Err(ParseFailure { index: 0, .. })));
+    assert!(matches!(parser.push("frob"), Ok(items) if items.is_empty()));
+    assert!(matches!(parser.push("-v"), Err(ParseFailure { index: 1, .. })));
+    assert!(!parser.is_waiting());
+
+    // A word that can't be an action with data fails at once
+    let mut parser: Parser<TestFlagType, TestActionType, _> = Parser::new(TestErrorType::Syntax);
+    assert!(matches!(parser.push("frob"), Err(ParseFailure { index: 0, .. })));
+    assert!(!parser.is_waiting());
+}
+
+#[test]
+fn push_parser_with_options() {
+    let policy = PositionalPolicy { min: 0, max: Some(1) };
+    let options = ParseOptions::new().short_clusters(true).greedy_values(true).positionals(policy);
+    let line = vec!["-vh", "add", "--level=2", "c", "-f", "a", "b"];
+    let mut parser = Parser::with_options(TestErrorType::Syntax, &options);
+    let mut items = Vec::new();
+    for token in &line {
+        items.extend(parser.push(token).unwrap());
+    }
+    assert!(parser.is_waiting());
+    let args: TestArguments = parser.finish().unwrap();
+    assert_eq!(args, TestArguments::parse_with(line, TestErrorType::Syntax, &options).unwrap());
+    assert_eq!(items.len(), 5);
+    assert_eq!(args.flags[3].values, vec!["a".to_string(), "b".to_string()]);
+
+    // Counts are only checked once every argument has been read
+    let mut parser: Parser<TestFlagType, TestActionType, _> =
+        Parser::with_options(TestErrorType::Syntax, &options);
+    assert!(parser.push("x").is_ok());
+    assert!(parser.push("y").is_ok());
+    assert!(matches!(parser.finish(), Err(ParseFailure { index: 1, .. })));
+}
+
+#[test]
+fn dangling_greedy_flag() {
+    let options = ParseOptions::new().greedy_values(true);
+    assert!(simulate_with(vec!["add", "-f"], &options).unwrap().flags[0].values.is_empty());
+    let options = options.require_values(true);
+    let failure = simulate_with(vec!["add", "-f"], &options).unwrap_err();
+    assert_eq!(failure.index, 1);
+    assert!(matches!(failure.error, TestErrorType::NoFlagVal));
+    assert!(simulate_with(vec!["-v", "add"], &options).is_ok());
+
+    // Also in a cluster, and reported as `ParseError::MissingValue`
+    let options = options.short_clusters(true);
+    let failure = simulate_with(vec!["-vf"], &options).unwrap_err();
+    assert!(matches!(failure.error, TestErrorType::NoFlagVal));
+    let result = TestArguments::parse_with(vec!["-f"], ParseError::UnknownFlag, &options);
+    assert_eq!(result.unwrap_err().error, ParseError::MissingValue);
+
+    let mut parser: Parser<TestFlagType, TestActionType, _> =
+        Parser::with_options(TestErrorType::Syntax, &options);
+    assert!(matches!(parser.push("-f"), Ok(items) if items.is_empty()));
+    assert!(matches!(parser.finish(), Err(ParseFailure { index: 0, .. })));
+}
+
+#[test]
+fn validate_args_dry_run() {
+    let line = [String::from("-v"), String::from("add"), String::from("--level=2")];
+    assert!(TestArguments::validate_args(&line, TestErrorType::Syntax).is_ok());
+    assert!(TestArguments::validate_args(&["add", "-v=x"], TestErrorType::Syntax).is_err());
+    assert!(TestArguments::validate_args::<&str, _>(&[], TestErrorType::Syntax).is_ok());
+}
+
+#[test]
+fn parse_flags_only() {
+    let flags = TestArguments::flags_only(&["-v", "--level=2"], TestErrorType::Syntax).unwrap();
+    assert_eq!(flags.len(), 2);
+    assert_eq!(flags[1].key, TestFlagType::Level);
+    let stray = TestArguments::flags_only(&["-v", "add"], TestErrorType::Syntax);
+    assert!(matches!(stray, Err(TestErrorType::Syntax)));
+}
+
+#[test]
+fn attached_short_values() {
+    let options = ParseOptions::new().attached_values(true).short_clusters(true);
+    let env_args = simulate_with(vec!["-ffile.txt", "-pa=b", "-vf"], &options).unwrap();
+    let expected = Flag {
+        key: TestFlagType::Files,
+        value: Some("file.txt".to_string()),
+        values: Vec::new(),
+        style: FlagStyle::Short,
+    };
+    assert_eq!(env_args.flags[0], expected);
+    assert_eq!(env_args.flags[1].value, Some("a=b".to_string()));
+    // `-v` takes no value, so `-vf` is still a cluster
+    assert_eq!(env_args.flags[2].key, TestFlagType::Verbose);
+    assert_eq!(env_args.flags[3].key, TestFlagType::Files);
+    // Long flags never take an attached value
+    assert!(simulate_with(vec!["--filesx"], &options).is_err());
+    assert!(simulate_with(vec!["-ffile.txt"], &ParseOptions::new()).is_err());
+}
+
+#[test]
+fn complete_at_point() {
+    let everything = complete::<TestFlagType, TestActionType>(&[], "");
+    assert_eq!(everything.len(), TestFlagType::known_keys().len() + TestActionType::known_keys().len());
+    assert_eq!(everything[1], Completion::Flag("--help"));
+    assert_eq!(everything[10], Completion::Action("add"));
+
+    let completions = complete::<TestFlagType, TestActionType>(&["add"], "--ver");
+    assert_eq!(completions, vec![Completion::Flag("--verbose")]);
+    let completions = complete::<TestFlagType, TestActionType>(&[], "r");
+    assert_eq!(completions, vec![Completion::Action("remove"), Completion::Action("r")]);
+    assert_eq!(complete::<TestFlagType, TestActionType>(&["-v", "-f"], ""), vec![Completion::File]);
+    // `-v` takes no value, and a flag given its value with `=` is done
+    assert_eq!(complete::<TestFlagType, TestActionType>(&["-v"], "e").len(), 2);
+    assert_eq!(complete::<TestFlagType, TestActionType>(&["-f=x"], "e").len(), 2);
+}
+
+#[test]
+fn alias_expansion() {
+    let options = ParseOptions::new().aliases(&[
+        ("ad", &["add"]),
+        ("ed", &["edit", "--level=1"]),
+        ("x", &["ad"]),
+    ]);
+    let env_args = simulate_with(vec!["ad", "-p=ad", "ed"], &options).unwrap();
+    assert_eq!(env_args.actions, vec![TestActionType::Add, TestActionType::Edit]);
+    // Flag values are never expanded
+    assert_eq!(env_args.all_values_of(TestFlagType::Print), vec!["ad"]);
+    assert_eq!(env_args.all_values_of(TestFlagType::Level), vec!["1"]);
+
+    // An alias of an alias is only expanded once by default
+    assert_eq!(simulate_with(vec!["add", "x"], &options).unwrap_err().index, 1);
+    let options = options.alias_depth(2);
+    assert_eq!(simulate_with(vec!["x"], &options).unwrap().actions, vec![TestActionType::Add]);
+
+    // Nor are greedy values, or anything after `--`
+    let options = options.greedy_values(true).positionals(PositionalPolicy::default());
+    let env_args = simulate_with(vec!["-f", "ad", "-v", "ad", "--", "ad"], &options).unwrap();
+    assert_eq!(env_args.flags[0].values, vec!["ad"]);
+    assert_eq!(env_args.actions, vec![TestActionType::Add]);
+    assert_eq!(env_args.positionals, vec!["ad"]);
+}
+
+// Flags and actions in one enum
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TestCommand {
+    Verbose,
+    Out,
+    Add,
+    Remove,
+}
+
+impl ArgumentType for TestCommand {
+    fn from_str<E: Error>(key: &str, error: E) -> Result<Self, E> {
+        match key {
+            "-v" | "--verbose" => Ok(Self::Verbose),
+            "-o" | "--out" => Ok(Self::Out),
+            "add" => Ok(Self::Add),
+            "remove" => Ok(Self::Remove),
+            _ => Err(error),
+        }
+    }
+}
+
+#[test]
+fn single_enum_for_flags_and_actions() {
+    let env_args = vec!["add", "-v", "--out=x", "remove"];
+    let env_args = SingleArguments::<TestCommand>::parse(env_args, TestErrorType::Syntax).unwrap();
+    assert_eq!(env_args.get(TestCommand::Out), FlagMatch::Value("x"));
+    assert_eq!(env_args.actions, vec![TestCommand::Add, TestCommand::Remove]);
+    assert!(SingleArguments::<TestCommand>::parse(vec!["frob"], TestErrorType::Syntax).is_err());
+}
+
+crate::arg_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub(crate) enum TestMacroFlag {
+        /// Print help
+        Help => ["-h", "--help"],
+        /// Print more
+        /// output
+        Verbose => ["-v", "--verbose",],
+        Quiet => ["-q"],
+    }
+}
+
+crate::arg_enum! {
+    #[derive(Debug, PartialEq)]
+    enum TestMacroAction {
+        Add => ["add", "a"]
+    }
+}
+
+#[test]
+fn arg_enum_macro() {
+    for key in TestMacroFlag::known_keys() {
+        assert!(TestMacroFlag::from_str(key, ParseError::UnknownFlag).is_ok());
+    }
+    assert_eq!(TestMacroFlag::known_keys().len(), 5);
+    assert_eq!(TestMacroFlag::variants().len(), 3);
+    assert_eq!(TestMacroFlag::Help.canonical_key(), "--help");
+    assert_eq!(TestMacroFlag::Quiet.canonical_key(), "-q");
+    assert_eq!(TestMacroFlag::Verbose.describe(), "Print more output");
+    assert_eq!(TestMacroFlag::Quiet.describe(), "");
+    assert_eq!(check::round_trip::<TestMacroFlag, _>(ParseError::UnknownFlag), Ok(()));
+
+    let env_args: Arguments<TestMacroFlag, TestMacroAction> =
+        Arguments::parse(vec!["a", "-q"], ParseError::UnknownFlag).unwrap();
+    assert_eq!(env_args.actions, vec![TestMacroAction::Add]);
+    assert_eq!(TestMacroAction::known_keys().len(), 2);
+}
+
+crate::error_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum TestMacroError {
+        /// Bad syntax
+        Syntax(code = 2) => "Improper syntax usage",
+        UnknownFlag => "Unknown flag",
+    }
+}
+
+#[test]
+fn error_enum_macro() {
+    assert_eq!(TestMacroError::Syntax.as_str(), "Improper syntax usage");
+    assert_eq!(TestMacroError::UnknownFlag.message(), "Unknown flag");
+    assert_eq!(TestMacroError::UnknownFlag.render(), "error: Unknown flag");
+    assert_eq!(TestMacroError::Syntax.exit_code(), 2);
+    assert_eq!(TestMacroError::UnknownFlag.exit_code(), 1);
+    assert_eq!(TestMacroError::Syntax.to_string(), "Improper syntax usage");
+
+    let failure = TestArguments::parse(vec!["--frob"], TestMacroError::UnknownFlag).unwrap_err();
+    assert_eq!(failure, TestMacroError::UnknownFlag);
+    #[cfg(feature = "std")]
+    {
+        let boxed: std::boxed::Box<dyn std::error::Error> = failure.into();
+        assert_eq!(boxed.to_string(), "Unknown flag");
+    }
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, PartialEq)]
+struct TestConfig {
+    verbose: bool,
+    output: std::path::PathBuf,
+    action: TestActionType,
+}
+
+#[cfg(feature = "std")]
+impl crate::FromArguments<TestFlagType, TestActionType> for TestConfig {
+    type Err = ParseError;
+    fn from_arguments(args: &TestArguments) -> Result<Self, ParseError> {
+        let output = args.last_value_of(TestFlagType::Files).ok_or(ParseError::MissingValue)?;
+        Ok(TestConfig {
+            verbose: args.contains_flag(TestFlagType::Verbose),
+            output: output.into(),
+            action: *args.require_action(ParseError::UnknownAction)?,
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn arguments_into_struct() {
+    let env_args = simulate(vec!["-v", "--files=out.txt", "edit"]).unwrap_or_default();
+    let config: TestConfig = env_args.into_struct().unwrap();
+    assert_eq!(
+        config,
+        TestConfig {
+            verbose: true,
+            output: "out.txt".into(),
+            action: TestActionType::Edit,
+        }
+    );
+
+    let env_args = simulate(vec!["edit"]).unwrap_or_default();
+    assert_eq!(env_args.into_struct::<TestConfig>(), Err(ParseError::MissingValue));
+    let env_args = simulate(vec!["--files=out.txt"]).unwrap_or_default();
+    assert_eq!(env_args.into_struct::<TestConfig>(), Err(ParseError::UnknownAction));
+}
+
+crate::arg_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum TestStdinAction {
+        Stdin => ["-"],
+    }
+}
+
+#[test]
+fn parse_dash_values() {
+    let env_args = simulate(vec!["--files=-", "-f=--"]).unwrap_or_default();
+    assert_eq!(env_args.flags[0].value, Some("-".to_string()));
+    assert_eq!(env_args.flags[1].value, Some("--".to_string()));
+    assert_eq!(env_args.flags[1].key, TestFlagType::Files);
+}
+
+#[test]
+fn parse_standalone_dash_as_action() {
+    let env_args: Arguments<TestFlagType, TestStdinAction> =
+        Arguments::parse(vec!["-v", "-"], ParseError::UnknownFlag).unwrap();
+    assert_eq!(env_args.actions, vec![TestStdinAction::Stdin]);
+    assert_eq!(env_args.flags.len(), 1);
+    assert!(simulate(vec!["-"]).is_err());
+
+    let mut items =
+        Arguments::<TestFlagType, TestStdinAction>::parse_iter(vec!["-"], ParseError::UnknownFlag);
+    assert!(matches!(items.next(), Some(Ok(ParsedItem::Action(TestStdinAction::Stdin)))));
+}
+
+#[derive(Debug, PartialEq)]
+enum TestValueError {
+    Missing,
+    Invalid,
+}
+
+impl From<std::num::ParseIntError> for TestValueError {
+    fn from(_: std::num::ParseIntError) -> Self {
+        Self::Invalid
+    }
+}
+
+impl From<std::convert::Infallible> for TestValueError {
+    fn from(never: std::convert::Infallible) -> Self {
+        match never {}
+    }
+}
+
+#[test]
+fn typed_value_u32() {
+    let env_args = simulate(vec!["-p=12", "-f"]).unwrap_or_default();
+    assert_eq!(env_args.typed_value::<u32>(TestFlagType::Print), Ok(Some(12)));
+    assert_eq!(env_args.typed_value::<u32>(TestFlagType::Files), Ok(None));
+    assert_eq!(env_args.typed_value::<u32>(TestFlagType::Level), Ok(None));
+    assert_eq!(env_args.typed_value_or(TestFlagType::Print, 4u32), Ok(12));
+    assert_eq!(env_args.typed_value_or(TestFlagType::Files, 4u32), Ok(4));
+    assert_eq!(env_args.typed_value_or(TestFlagType::Level, 4u32), Ok(4));
+    let required = |key| env_args.typed_value_required(key, 4u32, TestValueError::Missing);
+    assert_eq!(required(TestFlagType::Print), Ok(12));
+    assert_eq!(required(TestFlagType::Files), Err(TestValueError::Missing));
+    assert_eq!(required(TestFlagType::Level), Ok(4));
+
+    let env_args = simulate(vec!["-p=many"]).unwrap_or_default();
+    assert!(env_args.typed_value::<u32>(TestFlagType::Print).is_err());
+    assert!(env_args.typed_value_or(TestFlagType::Print, 4u32).is_err());
+    assert_eq!(
+        env_args.typed_value_required(TestFlagType::Print, 4u32, TestValueError::Missing),
+        Err(TestValueError::Invalid)
+    );
+}
+
+#[test]
+fn typed_value_path() {
+    use std::path::PathBuf;
+
+    let env_args = simulate(vec!["--files=out/a.txt", "-p"]).unwrap_or_default();
+    let default = || PathBuf::from("a.out");
+    assert_eq!(env_args.typed_value(TestFlagType::Files), Ok(Some(PathBuf::from("out/a.txt"))));
+    assert_eq!(env_args.typed_value::<PathBuf>(TestFlagType::Print), Ok(None));
+    assert_eq!(env_args.typed_value_or(TestFlagType::Print, default()), Ok(default()));
+    assert_eq!(env_args.typed_value_or(TestFlagType::Level, default()), Ok(default()));
+    assert_eq!(
+        env_args.typed_value_required(TestFlagType::Files, default(), TestValueError::Missing),
+        Ok(PathBuf::from("out/a.txt"))
+    );
+    assert_eq!(
+        env_args.typed_value_required(TestFlagType::Print, default(), TestValueError::Missing),
+        Err(TestValueError::Missing)
+    );
+    assert_eq!(
+        env_args.typed_value_required(TestFlagType::Level, default(), TestValueError::Missing),
+        Ok(default())
+    );
+}
+
+#[test]
+fn index_flag_value() {
+    let env_args = simulate(vec!["-p=a", "--print=b", "-f"]).unwrap_or_default();
+    assert_eq!(&env_args[TestFlagType::Print], "b");
+}
+
+#[test]
+#[should_panic(expected = "not passed with a value")]
+fn index_flag_without_value() {
+    let env_args = simulate(vec!["-f"]).unwrap_or_default();
+    let _ = &env_args[TestFlagType::Files];
+}
+
+#[test]
+fn arguments_into_items_and_back() {
+    let options = ParseOptions::new().positionals(PositionalPolicy::default());
+    let env_args = simulate_with(vec!["-v", "add", "a.txt", "--files=b"], &options).unwrap();
+    let items: Vec<ParsedRef<TestFlagType, TestActionType>> = (&env_args).into_iter().collect();
+    assert_eq!(items.len(), 4);
+    assert_eq!(items[1], ParsedRef::Action(&TestActionType::Add));
+    assert!(matches!(items[2], ParsedRef::Flag(flag) if flag.key == TestFlagType::Files));
+    assert_eq!(items[3], ParsedRef::Positional("a.txt"));
+
+    let rebuilt: TestArguments = items.into_iter().map(ParsedRef::cloned).collect();
+    assert_eq!(rebuilt, env_args);
+    let owned: Vec<_> = env_args.into_iter().collect();
+    assert_eq!(owned.into_iter().collect::<TestArguments>(), rebuilt);
+}
+
+#[test]
+fn arguments_extend() {
+    let mut env_args = TestArguments::default();
+    env_args.extend(vec![Flag {
+        key: TestFlagType::Verbose,
+        value: None,
+        values: Vec::new(),
+        style: FlagStyle::Short,
+    }]);
+    env_args.extend(vec![ParsedItem::Action(TestActionType::Edit)]);
+    assert_eq!(env_args, simulate(vec!["-v", "edit"]).unwrap_or_default());
+}
+
+#[test]
+fn value_or_default() {
+    let env_args = simulate(vec!["--print=a", "-f"]).unwrap_or_default();
+    assert_eq!(env_args.value_or(TestFlagType::Print, "x"), "a");
+    assert_eq!(env_args.value_or(TestFlagType::Files, "x"), "x");
+    assert_eq!(env_args.value_or(TestFlagType::Level, "x"), "x");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn parse_timed_large_input() {
+    let env_args: Vec<&str> =
+        ["-v", "--print=a", "add"].iter().copied().cycle().take(30_000).collect();
+    let (env_args, elapsed) =
+        TestArguments::parse_timed(env_args, ParseError::UnknownFlag).unwrap();
+    assert_eq!(env_args.actions.len(), 10_000);
+    assert!(elapsed > std::time::Duration::ZERO);
+}
+
+#[test]
+fn add_flags_and_actions() {
+    let mut env_args = TestArguments::default();
+    env_args.add_flag(TestFlagType::Verbose, None);
+    env_args.add_flag(TestFlagType::Print, Some("a".to_string()));
+    env_args.add_action(TestActionType::Add);
+    assert_eq!(env_args, simulate(vec!["--verbose", "add", "--print=a"]).unwrap_or_default());
+}
+
+#[test]
+fn remove_repeated_flag() {
+    let mut env_args = simulate(vec!["-p=a", "-v", "--print=b", "-p"]).unwrap_or_default();
+    assert_eq!(env_args.remove_flag(&TestFlagType::Print), 3);
+    assert_eq!(env_args.flags.len(), 1);
+    assert_eq!(env_args.flags[0].key, TestFlagType::Verbose);
+    assert_eq!(env_args.remove_flag(&TestFlagType::Print), 0);
+}
+
+#[test]
+fn take_first_flag() {
+    let mut env_args = simulate(vec!["-p=a", "-v", "add", "--print=b"]).unwrap_or_default();
+    let taken = env_args.take_flag(TestFlagType::Print).unwrap();
+    assert_eq!(taken.value, Some("a".to_string()));
+    assert_eq!(env_args.all_values_of(TestFlagType::Print), vec!["b"]);
+    assert_eq!(env_args.global_flags().len(), 1);
+
+    assert!(env_args.take_flag(TestFlagType::Verbose).is_some());
+    assert!(!env_args.contains_flag(TestFlagType::Verbose));
+    assert!(env_args.take_flag(TestFlagType::Verbose).is_none());
+}
+
+#[test]
+fn parse_slash_flags() {
+    let options = ParseOptions::new().slash_flags(true).positionals(PositionalPolicy::default());
+    let env_args =
+        simulate_with(vec!["/verbose", "/files:out.txt", "/p", "/tmp"], &options).unwrap();
+    assert_eq!(env_args.flags[0].key, TestFlagType::Verbose);
+    assert_eq!(env_args.flags[0].style, FlagStyle::Long);
+    assert_eq!(env_args.flags[1].key, TestFlagType::Files);
+    assert_eq!(env_args.flags[1].value, Some("out.txt".to_string()));
+    assert_eq!(env_args.flags[2].style, FlagStyle::Short);
+    assert_eq!(env_args.positionals, vec!["/tmp".to_string()]);
+
+    assert!(simulate_with(vec!["/verbose"], &ParseOptions::new()).is_err());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TestSecretFlag {
+    Token,
+    Name,
+}
+
+impl ArgumentType for TestSecretFlag {
+    fn from_str<E: Error>(key: &str, error: E) -> Result<Self, E> {
+        match key {
+            "--token" => Ok(Self::Token),
+            "--name" => Ok(Self::Name),
+            _ => Err(error),
+        }
+    }
+    fn sensitive(&self) -> bool {
+        matches!(self, Self::Token)
+    }
+}
+
+#[test]
+fn redacted_debug_masks_sensitive_values() {
+    let env_args: Arguments<TestSecretFlag, TestActionType> =
+        Arguments::parse(vec!["--token=abc123", "--name=me", "add"], ParseError::UnknownFlag)
+            .unwrap();
+    assert_eq!(
+        env_args.flags[0].redacted_debug(),
+        r#"Flag { key: Token, value: Some("***"), values: [], style: Long }"#
+    );
+    assert_eq!(env_args.flags[1].redacted_debug(), format!("{:?}", env_args.flags[1]));
+    assert_eq!(env_args.flags[0].value, Some("abc123".to_string()));
+
+    let debug = env_args.redacted_debug();
+    assert!(!debug.contains("abc123"));
+    assert_eq!(debug, format!("{:?}", env_args).replace("abc123", "***"));
+}
+
+#[test]
+fn nth_value_of_repeated_flag() {
+    let env_args = simulate(vec!["-p=a", "-v", "--print=b", "-p=c", "-f"]).unwrap_or_default();
+    assert_eq!(env_args.nth_value_of(TestFlagType::Print, 1), Some("b"));
+    assert_eq!(env_args.nth_value_of(TestFlagType::Print, 0), Some("a"));
+    assert_eq!(env_args.nth_value_of(TestFlagType::Print, 3), None);
+    assert_eq!(env_args.nth_value_of(TestFlagType::Files, 0), None);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TestRenamedFlag {
+    Color,
+    Colour,
+}
+
+impl ArgumentType for TestRenamedFlag {
+    fn from_str<E: Error>(key: &str, error: E) -> Result<Self, E> {
+        match key {
+            "--color" => Ok(Self::Color),
+            "--colour" => Ok(Self::Colour),
+            _ => Err(error),
+        }
+    }
+    fn deprecated(&self) -> Option<&'static str> {
+        match self {
+            Self::Colour => Some("use --color instead"),
+            Self::Color => None,
+        }
+    }
+}
+
+#[test]
+fn deprecated_flag_warns_once_per_occurrence() {
+    let warnings = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let seen = std::sync::Arc::clone(&warnings);
+    let options = ParseOptions::new()
+        .aliases(&[("fancy", &["--colour=always"])])
+        .on_warning(move |warning| seen.lock().unwrap().push(warning));
+    let env_args: Arguments<TestRenamedFlag, TestActionType> = Arguments::parse_with(
+        vec!["--colour=auto", "add", "--color", "fancy"],
+        ParseError::UnknownFlag,
+        &options,
+    )
+    .unwrap();
+
+    assert_eq!(env_args.flags.len(), 3);
+    assert_eq!(env_args.flags[0].key, TestRenamedFlag::Colour);
+    assert_eq!(env_args.flags[0].value, Some("auto".to_string()));
+    let warnings = warnings.lock().unwrap();
+    assert_eq!(warnings.len(), 2);
+    assert_eq!(
+        warnings[0],
+        crate::Warning {
+            token: "--colour=auto".to_string(),
+            index: 0,
+            message: "use --color instead",
+        }
+    );
+    assert_eq!(warnings[1].token, "--colour=always");
+    assert_eq!(warnings[1].index, 3);
+}
+
+#[test]
+fn options_are_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<ParseOptions>();
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn parse_os_strings() {
+    use std::ffi::OsString;
+
+    let env_args: Vec<OsString> = vec!["-v".into(), "--print=é".into(), "add".into()];
+    let env_args = TestArguments::parse_os(env_args, ParseError::UnknownFlag).unwrap();
+    assert_eq!(env_args, simulate(vec!["-v", "--print=é", "add"]).unwrap_or_default());
+}
+
+#[cfg(all(feature = "std", unix))]
+#[test]
+fn parse_os_rejects_invalid_utf8() {
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+
+    let env_args = vec![OsString::from("-v"), OsString::from_vec(vec![0x61, 0xff])];
+    let result = TestArguments::parse_os(env_args, ParseError::UnknownFlag);
+    assert_eq!(result, Err(ParseError::UnknownFlag));
+}
+
+#[test]
+fn prescan_for_keys() {
+    let keys = ["-h", "--help", "--version"];
+    assert_eq!(TestArguments::prescan(&["-v", "frob", "--version", "-h"], &keys), Some(2));
+    assert_eq!(TestArguments::prescan(&["-v", "--", "--help"], &keys), None);
+    assert_eq!(TestArguments::prescan(&["-v", "--help=x", "add"], &keys), None);
+    assert_eq!(TestArguments::prescan::<&str>(&[], &keys), None);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TestAliasedFlag {
+    Verbose,
+    Output,
+}
+
+impl ArgumentType for TestAliasedFlag {
+    fn from_str<E: Error>(key: &str, error: E) -> Result<Self, E> {
+        match key {
+            "--verbose" => Ok(Self::Verbose),
+            "--output" => Ok(Self::Output),
+            _ => Err(error),
+        }
+    }
+    fn aliases() -> &'static [(&'static str, &'static str)] {
+        &[("-v", "--verbose"), ("--out", "--output")]
+    }
+}
+
+#[test]
+fn flag_alias_table() {
+    let env_args: Arguments<TestAliasedFlag, TestActionType> =
+        Arguments::parse(vec!["-v", "--out=a.txt", "add"], ParseError::UnknownFlag).unwrap();
+    assert_eq!(env_args.flags[0].key, TestAliasedFlag::Verbose);
+    assert_eq!(env_args.flags[1].key, TestAliasedFlag::Output);
+    assert_eq!(env_args.flags[1].value, Some("a.txt".to_string()));
+
+    let options = ParseOptions::new().strip_prefix(true);
+    let env_args: Arguments<TestAliasedFlag, TestActionType> =
+        Arguments::parse_with(vec!["--out=b"], ParseError::UnknownFlag, &options).unwrap();
+    assert_eq!(env_args.flags[0].key, TestAliasedFlag::Output);
+}
+
+#[test]
+fn global_and_trailing_flags() {
+    use TestFlagType::*;
+
+    let env_args =
+        simulate(vec!["-v", "-h", "add", "-p=a", "edit", "-f", "-l=1"]).unwrap_or_default();
+    let keys = |flags: &[Flag<TestFlagType>]| flags.iter().map(|flag| flag.key).collect::<Vec<_>>();
+    assert_eq!(keys(env_args.global_flags()), vec![Verbose, Help]);
+    assert_eq!(keys(env_args.flags_after_action(0)), vec![Print]);
+    assert_eq!(keys(env_args.flags_after_action(1)), vec![Files, Level]);
+    assert!(env_args.flags_after_action(2).is_empty());
+
+    let env_args = simulate(vec!["-v", "-h"]).unwrap_or_default();
+    assert_eq!(env_args.global_flags().len(), 2);
+    assert!(env_args.flags_after_action(0).is_empty());
+}
+
+#[test]
+fn removing_flags_keeps_them_ordered_around_actions() {
+    let mut env_args = simulate(vec!["-p=a", "-v", "add", "-p=b", "-h"]).unwrap_or_default();
+    assert_eq!(env_args.remove_flag(&TestFlagType::Print), 2);
+    assert_eq!(env_args.global_flags().len(), 1);
+    assert_eq!(env_args.flags_after_action(0)[0].key, TestFlagType::Help);
+
+    let options = ParseOptions::new().duplicate_flags(DuplicatePolicy::LastWins);
+    let env_args = simulate_with(vec!["-v", "add", "-v"], &options).unwrap();
+    assert!(env_args.global_flags().is_empty());
+    assert_eq!(env_args.flags_after_action(0).len(), 1);
+}
+
+#[test]
+fn partition_known_and_unknown() {
+    let (known, unknown) =
+        TestArguments::partition_args(&["-v", "--frob", "add", "x.txt", "--print=a", "-v=1", ""]);
+    assert_eq!(known, vec!["-v", "add", "--print=a"]);
+    assert_eq!(unknown, vec!["--frob", "x.txt", "-v=1", ""]);
+
+    let options = ParseOptions::new().short_clusters(true).allow_abbreviation(true);
+    let (known, unknown) = TestArguments::partition_args_with(&["-vh", "--lev=2", "-vq", "x"], &options);
+    assert_eq!(known, vec!["-vh", "--lev=2"]);
+    assert_eq!(unknown, vec!["-vq", "x"]);
+    let flags = TestArguments::flags_only_with(&["-vh", "--lev=2"], TestErrorType::Syntax, &options);
+    assert_eq!(flags.unwrap().len(), 3);
+    assert!(TestArguments::flags_only(&["-vh"], TestErrorType::Syntax).is_err());
+}
+
+#[test]
+fn posix_mode_stops_at_first_operand() {
+    let options = ParseOptions::new().posix_mode(true);
+    let env_args = simulate_with(vec!["-h", "add", "-v", "edit", "--"], &options).unwrap();
+    assert_eq!(env_args.flags.len(), 1);
+    assert_eq!(env_args.actions, vec![TestActionType::Add]);
+    assert_eq!(env_args.positionals, vec!["-v", "edit", "--"]);
+
+    let env_args = simulate_with(vec!["add", "-v"], &options).unwrap();
+    assert_eq!(env_args.actions, vec![TestActionType::Add]);
+    assert_eq!(env_args.positionals, vec!["-v"]);
+    let env_args = simulate_with(vec!["add", "-v"], &ParseOptions::new()).unwrap();
+    assert_eq!(env_args.actions, vec![TestActionType::Add]);
+    assert_eq!(env_args.flags[0].key, TestFlagType::Verbose);
+
+    let env_args = simulate_with(vec!["-v", "--", "-h"], &options).unwrap();
+    assert_eq!(env_args.positionals, vec!["-h"]);
+}
+
+#[test]
+fn require_action_count_bounds() {
+    let count = |env_args: Vec<&str>| {
+        simulate(env_args).unwrap_or_default().require_action_count(1, 3, ParseError::UnknownAction)
+    };
+    assert_eq!(count(vec!["-v"]), Err(ParseError::UnknownAction));
+    assert_eq!(count(vec!["add"]), Ok(()));
+    assert_eq!(count(vec!["add", "edit", "remove"]), Ok(()));
+    assert_eq!(count(vec!["add", "edit", "remove", "a"]), Err(ParseError::UnknownAction));
+}
+
+#[test]
+fn dedup_flags_after_parse() {
+    let env_args = vec!["-p=a", "-v", "-p=b", "add", "-h", "-p=c"];
+    let values = |env_args: &TestArguments| {
+        env_args.flags.iter().map(|flag| (flag.key, flag.value.clone())).collect::<Vec<_>>()
+    };
+
+    let mut first = simulate(env_args.clone()).unwrap_or_default();
+    first.dedup_flags_keep_first();
+    assert_eq!(
+        values(&first),
+        vec![
+            (TestFlagType::Print, Some("a".to_string())),
+            (TestFlagType::Verbose, None),
+            (TestFlagType::Help, None),
+        ]
+    );
+    assert_eq!(first.global_flags().len(), 2);
+
+    let mut last = simulate(env_args).unwrap_or_default();
+    last.dedup_flags_keep_last();
+    assert_eq!(
+        values(&last),
+        vec![
+            (TestFlagType::Verbose, None),
+            (TestFlagType::Help, None),
+            (TestFlagType::Print, Some("c".to_string())),
+        ]
+    );
+    assert_eq!(last.flags_after_action(0).len(), 2);
+}
+
+#[test]
+fn flag_set_membership_and_union() {
+    let set = |env_args: Vec<&str>| {
+        let env_args: Arguments<TestMacroFlag, TestMacroAction> =
+            Arguments::parse(env_args, ParseError::UnknownFlag).unwrap();
+        env_args.flag_set().unwrap()
+    };
+    let first = set(vec!["-v"]);
+    assert!(first.contains(TestMacroFlag::Verbose));
+    assert!(!first.contains(TestMacroFlag::Help));
+
+    let both = first.union(set(vec!["--help", "-v"]));
+    assert!(both.contains(TestMacroFlag::Help));
+    assert!(both.contains(TestMacroFlag::Verbose));
+    assert_eq!(both.intersection(set(vec!["-h"])), set(vec!["--help"]));
+    assert!(first.intersection(set(vec!["-h"])).is_empty());
+}
+
+#[test]
+fn flag_set_without_bits() {
+    // TestFlagType doesn't implement `bit`, so its keys are all past bit 63
+    let env_args = simulate(vec!["-v"]).unwrap_or_default();
+    assert_eq!(env_args.flag_set(), None);
+    assert_eq!(TestArguments::new().flag_set(), Some(crate::FlagSet::new()));
+    assert!(!crate::FlagSet::new().insert(TestFlagType::Verbose));
+}
+
+#[test]
+fn parse_with_warnings_collects_deprecations() {
+    type RenamedArguments = Arguments<TestRenamedFlag, TestActionType>;
+    let outcome = RenamedArguments::parse_with_warnings(
+        vec!["--color", "add", "--colour=auto"],
+        ParseError::UnknownFlag,
+    )
+    .unwrap();
+    assert_eq!(outcome.args.flags.len(), 2);
+    assert_eq!(outcome.args.flags[1].key, TestRenamedFlag::Colour);
+    assert_eq!(outcome.warnings.len(), 1);
+    assert_eq!(outcome.warnings[0].token, "--colour=auto");
+    assert_eq!(outcome.warnings[0].index, 2);
+
+    let outcome =
+        RenamedArguments::parse_with_warnings(vec!["--color"], ParseError::UnknownFlag).unwrap();
+    assert!(outcome.warnings.is_empty());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn flag_map_groups_repeats() {
+    let env_args =
+        simulate(vec!["-p=a", "-v", "-p", "--print=b", "-f=x", "-f"]).unwrap_or_default();
+    let map = env_args.flag_map();
+    assert_eq!(map.len(), 3);
+    let print = vec![Some("a".to_string()), None, Some("b".to_string())];
+    assert_eq!(map[&TestFlagType::Print], print);
+    assert_eq!(map[&TestFlagType::Verbose], vec![None]);
+    assert_eq!(map[&TestFlagType::Files], vec![Some("x".to_string()), None]);
+
+    let last = env_args.flag_map_last();
+    assert_eq!(last[&TestFlagType::Print], Some("b".to_string()));
+    assert_eq!(last[&TestFlagType::Verbose], None);
+    assert_eq!(last[&TestFlagType::Files], Some("x".to_string()));
+    assert_eq!(env_args.flags.len(), 6);
+}
+
+crate::arg_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum TestNameFlag {
+        Name => ["-n", "--name"],
+    }
+}
+
+#[test]
+fn parse_dashless_flags() {
+    let options = ParseOptions::new().dashless_flags(true).positionals(PositionalPolicy::default());
+    let env_args: Arguments<TestNameFlag, TestMacroAction> =
+        Arguments::parse_with(vec!["name=bob", "add", "age=3"], ParseError::UnknownFlag, &options)
+            .unwrap();
+    assert_eq!(env_args.flags.len(), 1);
+    assert_eq!(env_args.flags[0].key, TestNameFlag::Name);
+    assert_eq!(env_args.flags[0].value, Some("bob".to_string()));
+    assert_eq!(env_args.actions, vec![TestMacroAction::Add]);
+    assert_eq!(env_args.positionals, vec!["age=3".to_string()]);
+
+    let env_args = simulate_with(vec!["-v", "--", "print=x"], &options).unwrap();
+    assert_eq!(env_args.flags.len(), 1);
+    assert!(simulate(vec!["print=x"]).is_err());
+}
+
+#[test]
+fn passthrough_pairs_after_terminator() {
+    let options = ParseOptions::new().passthrough_pairs(true);
+    let env_args = simulate_with(vec!["-v", "--", "a=1", "b=2"], &options).unwrap();
+    assert_eq!(env_args.flags.len(), 1);
+    assert_eq!(env_args.flags[0].key, TestFlagType::Verbose);
+    let pairs = vec![("a".to_string(), "1".to_string()), ("b".to_string(), "2".to_string())];
+    assert_eq!(env_args.passthrough, pairs);
+    assert!(env_args.positionals.is_empty());
+    assert_eq!(env_args.to_args(), vec!["--verbose", "--", "a=1", "b=2"]);
+
+    assert!(simulate_with(vec!["--", "a=1", "-v"], &options).is_err());
+    // Without the mode, `--` is looked up like any other flag
+    assert!(simulate_with(vec!["-v", "--", "a=1"], &ParseOptions::new()).is_err());
+}
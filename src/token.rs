@@ -0,0 +1,80 @@
+//! How the parser classifies a single argument before looking up its key,
+//! for tools such as syntax highlighters that don't need a full parse.
+
+use crate::ParseOptions;
+
+/// The kind of a single argument, as returned by `classify`. Keys keep
+/// their dashes, such as `"--out"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind<'a> {
+    /// A flag written with a single dash, such as `-v` or `-o=path`
+    ShortFlag {
+        /// The key, up to the value separator
+        key: &'a str,
+        /// The value after the separator, if there is one
+        value: Option<&'a str>,
+    },
+    /// A flag written with two dashes, such as `--verbose` or `--out=path`
+    LongFlag {
+        /// The key, up to the value separator
+        key: &'a str,
+        /// The value after the separator, if there is one
+        value: Option<&'a str>,
+    },
+    /// A lone `--`
+    Terminator,
+    /// A lone `-`, which usually stands for standard input, and is parsed
+    /// as an action
+    StdinDash,
+    /// Anything else, such as an action, including an empty argument
+    Word,
+}
+
+/// Classifies an argument the same way `Arguments::parse_with` does.
+///
+/// The value is split off at the first `ParseOptions::value_separator`,
+/// so it may contain the separator itself, and is unquoted if
+/// `ParseOptions::strip_quotes` is enabled.
+///
+/// # Examples
+///
+/// ```
+/// use adante::token::{classify, TokenKind};
+/// use adante::ParseOptions;
+///
+/// let options = ParseOptions::new();
+/// assert_eq!(
+///     classify("--out=a=b", &options),
+///     TokenKind::LongFlag { key: "--out", value: Some("a=b") }
+/// );
+/// assert_eq!(classify("-", &options), TokenKind::StdinDash);
+/// assert_eq!(classify("add", &options), TokenKind::Word);
+/// ```
+pub fn classify<'a>(token: &'a str, options: &ParseOptions) -> TokenKind<'a> {
+    match token {
+        "--" => return TokenKind::Terminator,
+        "-" => return TokenKind::StdinDash,
+        _ if !token.starts_with('-') => return TokenKind::Word,
+        _ => (),
+    }
+    let (key, value) = match token.split_once(options.separator()) {
+        Some((key, value)) if options.strips_quotes() => (key, Some(unquote(value))),
+        Some((key, value)) => (key, Some(value)),
+        None => (token, None),
+    };
+    if token.starts_with("--") {
+        TokenKind::LongFlag { key, value }
+    } else {
+        TokenKind::ShortFlag { key, value }
+    }
+}
+
+// Removes one balanced pair of surrounding quotes, if there is one
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''].iter() {
+        if value.len() >= 2 && value.starts_with(*quote) && value.ends_with(*quote) {
+            return &value[1..(value.len() - 1)];
+        }
+    }
+    value
+}
@@ -0,0 +1,153 @@
+use crate::{ArgumentType, Arguments};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// A rule checked by `Arguments::validate` after a successful parse.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rule<F> {
+    /// The flag must be passed
+    RequiredFlag(F),
+    /// Every occurrence of the flag must have a value
+    RequiresValue(F),
+    /// At most one of the flags may be passed
+    Conflicts(Vec<F>),
+    /// If the first flag is passed, the second must be too
+    Requires(F, F),
+    /// Exactly one action must be passed
+    ExactlyOneAction,
+    /// No more than this many positional arguments may be passed
+    MaxPositionals(usize),
+}
+
+/// Every rule an `Arguments` object broke, as returned by
+/// `Arguments::validate`.
+///
+/// Its `Display` impl writes one message per line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationReport<F> {
+    /// Each broken rule along with a message describing it, such as
+    /// `"'--out' requires a value"`, in the order the rules were given
+    pub violations: Vec<(Rule<F>, String)>,
+}
+
+impl<F> fmt::Display for ValidationReport<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (_, message)) in self.violations.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", message)?;
+        }
+        Ok(())
+    }
+}
+
+impl<F: ArgumentType + PartialEq + Clone, A, V> Arguments<F, A, V> {
+    /// Checks the parsed arguments against `rules`, reporting every rule
+    /// that's broken rather than stopping at the first, so all the problems
+    /// can be shown at once.
+    ///
+    /// Flags are named in the messages by `ArgumentType::canonical_key`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use adante::{ArgumentType, Arguments, ParseError, Rule};
+    ///
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum FlagType {
+    ///     Quiet,
+    ///     Verbose,
+    /// }
+    /// impl ArgumentType for FlagType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///                                -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "-q" | "--quiet" => Ok(Self::Quiet),
+    ///             "-v" | "--verbose" => Ok(Self::Verbose),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    ///     fn canonical_key(&self) -> &'static str {
+    ///         match self {
+    ///             Self::Quiet => "--quiet",
+    ///             Self::Verbose => "--verbose",
+    ///         }
+    ///     }
+    /// }
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum ActionType {
+    ///     Add,
+    /// }
+    /// impl ArgumentType for ActionType {
+    ///     fn from_str<ErrorType>(key: &str, error: ErrorType)
+    ///         -> Result<Self, ErrorType> {
+    ///         match key {
+    ///             "a" | "add" => Ok(Self::Add),
+    ///             _ => Err(error),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let args: Arguments<FlagType, ActionType> =
+    ///     Arguments::parse(vec!["-q", "-v"], ParseError::UnknownFlag).unwrap();
+    /// let rules = [Rule::Conflicts(vec![FlagType::Quiet, FlagType::Verbose])];
+    ///
+    /// let report = args.validate(&rules).unwrap_err();
+    /// assert_eq!(report.to_string(), "'--quiet' conflicts with '--verbose'");
+    /// ```
+    pub fn validate(&self, rules: &[Rule<F>]) -> Result<(), ValidationReport<F>> {
+        let violations: Vec<(Rule<F>, String)> = rules
+            .iter()
+            .filter_map(|rule| self.check_rule(rule).map(|message| (rule.clone(), message)))
+            .collect();
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationReport { violations })
+        }
+    }
+    // Returns the message for a broken rule, or `None` if it holds
+    fn check_rule(&self, rule: &Rule<F>) -> Option<String> {
+        let passed = |key: &F| self.flags.iter().any(|flag| flag.key == *key);
+        match rule {
+            Rule::RequiredFlag(key) if !passed(key) => {
+                Some(format!("missing required flag '{}'", key.canonical_key()))
+            }
+            Rule::RequiresValue(key)
+                if self.flags.iter().any(|flag| flag.key == *key && flag.value.is_none()) =>
+            {
+                Some(format!("'{}' requires a value", key.canonical_key()))
+            }
+            Rule::Conflicts(keys) => {
+                let found: Vec<&str> =
+                    keys.iter().filter(|key| passed(key)).map(F::canonical_key).collect();
+                match found.split_first() {
+                    Some((first, rest)) if !rest.is_empty() => Some(format!(
+                        "'{}' conflicts with '{}'",
+                        first,
+                        rest.join("', '")
+                    )),
+                    _ => None,
+                }
+            }
+            Rule::Requires(key, needed) if passed(key) && !passed(needed) => Some(format!(
+                "'{}' requires '{}'",
+                key.canonical_key(),
+                needed.canonical_key()
+            )),
+            Rule::ExactlyOneAction if self.actions.len() != 1 => Some(format!(
+                "expected exactly one action, found {}",
+                self.actions.len()
+            )),
+            Rule::MaxPositionals(max) if self.positionals.len() > *max => Some(format!(
+                "expected at most {} positional arguments, found {}",
+                max,
+                self.positionals.len()
+            )),
+            _ => None,
+        }
+    }
+}